@@ -0,0 +1,85 @@
+# ask shell integration for bash.
+#
+# Records the last failed command, its exit status and directory so
+# `ask --explain-last` can explain it, and binds Ctrl-G to replace the command
+# line with the command `ask` suggests for it. Add to ~/.bashrc:
+#
+#   eval "$(ask shell-init bash)"
+#
+# Set ASK_CAPTURE_STDERR=1 before sourcing to also record stderr. It reroutes
+# the stderr of the whole shell through `tee`, so programs no longer see a
+# terminal on stderr, which can turn off colors and progress bars, and output
+# may be reordered.
+
+__ask_state_dir="${ASK_STATE_DIR:-${XDG_STATE_HOME:-$HOME/.local/state}/ask}"
+mkdir -p "$__ask_state_dir"
+
+__ask_capture=
+if [ "${ASK_CAPTURE_STDERR:-0}" = 1 ]; then
+    __ask_capture=1
+    exec 2> >(tee -a "$__ask_state_dir/stderr.live" >&2)
+fi
+
+# Whether `$1`, a command run by the DEBUG trap, is one of PROMPT_COMMAND
+__ask_in_prompt_command() {
+    local entry entries IFS=$'\n;'
+    read -rd '' -a entries <<< "${PROMPT_COMMAND[*]}"
+    for entry in "${entries[@]}"; do
+        entry="${entry#"${entry%%[![:space:]]*}"}"
+        entry="${entry%"${entry##*[![:space:]]}"}"
+        [ "$entry" = "$1" ] && return 0
+    done
+    return 1
+}
+
+__ask_preexec() {
+    # Runs before every simple command, only the first one typed at the
+    # prompt counts, not the rest of the line nor other prompt hooks
+    [ -n "$COMP_LINE" ] && return
+    [ -n "$__ask_at_prompt" ] || return
+    case "$BASH_COMMAND" in __ask_precmd* | __ask_widget*) return ;; esac
+    __ask_in_prompt_command "$BASH_COMMAND" && return
+    __ask_at_prompt=
+    __ask_in_command=1
+    __ask_command=$(HISTTIMEFORMAT='' history 1 | sed 's/^ *[0-9]* *//')
+    [ -n "$__ask_command" ] || __ask_command=$BASH_COMMAND
+    [ -n "$__ask_capture" ] && : > "$__ask_state_dir/stderr.live"
+}
+
+__ask_precmd() {
+    local exit_status=$?
+    if [ -n "$__ask_in_command" ] && [ "$exit_status" -ne 0 ]; then
+        printf '%s\n' "$__ask_command" > "$__ask_state_dir/command"
+        printf '%s\n' "$exit_status" > "$__ask_state_dir/status"
+        printf '%s\n' "$PWD" > "$__ask_state_dir/cwd"
+        if [ -n "$__ask_capture" ]; then
+            cp "$__ask_state_dir/stderr.live" "$__ask_state_dir/stderr"
+        else
+            rm -f "$__ask_state_dir/stderr"
+        fi
+    fi
+    __ask_in_command=
+    __ask_at_prompt=1
+    return $exit_status
+}
+
+__ask_at_prompt=
+trap '__ask_preexec' DEBUG
+# Every entry of an array PROMPT_COMMAND (bash 5.1+) sees the exit status of
+# the command, in a string it has to come first
+if [[ "$(declare -p PROMPT_COMMAND 2>/dev/null)" == "declare -a"* ]]; then
+    PROMPT_COMMAND+=(__ask_precmd)
+else
+    PROMPT_COMMAND="__ask_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+
+__ask_widget() {
+    [ -n "$READLINE_LINE" ] || return
+    local suggestion
+    suggestion=$(ask --cmd "$READLINE_LINE" </dev/null 2>/dev/null) || return
+    [ -n "$suggestion" ] || return
+    READLINE_LINE=$suggestion
+    READLINE_POINT=${#READLINE_LINE}
+}
+
+bind -x '"\C-g": __ask_widget'
@@ -0,0 +1,39 @@
+# ask shell integration for fish.
+#
+# Records the last failed command, its exit status and directory so
+# `ask --explain-last` can explain it, and binds Ctrl-G to replace the command
+# line with the command `ask` suggests for it. Add to ~/.config/fish/config.fish:
+#
+#   ask shell-init fish | source
+#
+# fish cannot redirect its own stderr, so stderr is not captured.
+
+if set -q ASK_STATE_DIR
+    set -g __ask_state_dir $ASK_STATE_DIR
+else if set -q XDG_STATE_HOME
+    set -g __ask_state_dir $XDG_STATE_HOME/ask
+else
+    set -g __ask_state_dir $HOME/.local/state/ask
+end
+mkdir -p $__ask_state_dir
+
+function __ask_postexec --on-event fish_postexec
+    set -l exit_status $status
+    if test $exit_status -ne 0
+        printf '%s\n' $argv[1] >$__ask_state_dir/command
+        printf '%s\n' $exit_status >$__ask_state_dir/status
+        printf '%s\n' $PWD >$__ask_state_dir/cwd
+        rm -f $__ask_state_dir/stderr
+    end
+end
+
+function __ask_widget
+    set -l line (commandline)
+    test -n "$line"; or return
+    set -l suggestion (ask --cmd "$line" </dev/null 2>/dev/null | string collect)
+    and test -n "$suggestion"
+    and commandline -r -- $suggestion
+    commandline -f repaint
+end
+
+bind \cg __ask_widget
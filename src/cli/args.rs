@@ -1,13 +1,53 @@
-use crate::services::request::ReasoningEffort;
-use clap::Parser;
+use super::complete;
+use ask::models::code::Selector;
+use ask::models::truncate::TruncateStrategy;
+use ask::services::ledger::GroupBy;
+use ask::services::output::{CodeOptions, OutputFormat};
+use ask::services::question::{AskOptions, Mode, RequestOptions};
+use ask::services::request::ReasoningEffort;
+use ask::services::shell::Shell;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::engine::ArgValueCompleter;
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn parse_json_object(s: &str) -> Result<serde_json::Value, String> {
+    match serde_json::from_str(s) {
+        Ok(value @ serde_json::Value::Object(_)) => Ok(value),
+        Ok(_) => Err("expected a JSON object".to_string()),
+        Err(e) => Err(format!("invalid JSON: {}", e)),
+    }
+}
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
 pub struct Cli {
-    /// Enable configuration mode
-    #[arg(long, help = "Configure the application")]
+    /// Deprecated alias of `ask config`
+    #[arg(long, hide = true)]
     pub configure: bool,
 
+    #[command(flatten)]
+    pub chat: ChatArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// The first word of the question when it names a subcommand. Options are
+    /// not mixed with subcommands, so clap reads `ask -v chat q` as the question
+    /// `chat q`.
+    pub fn misplaced_subcommand(&self) -> Option<&str> {
+        let word = self.chat.input.first()?;
+        (self.command.is_none() && Cli::command().find_subcommand(word).is_some())
+            .then_some(word.as_str())
+    }
+}
+
+// Options of a question, given to `ask` itself or to `ask chat`. Not a doc
+// comment, clap would use it as the about text of `ask`.
+#[derive(Args, Debug)]
+pub struct ChatArgs {
     #[arg(
         short = 'r',
         long,
@@ -30,5 +70,408 @@ pub struct Cli {
     )]
     pub verbose: bool,
 
-    pub input: Option<String>,
+    #[arg(
+        long,
+        help = "How to shrink piped input that exceeds the context window",
+        long_help = "How to shrink piped input that exceeds the model's context window:\n\
+                     head - Keep the beginning\n\
+                     tail - Keep the end\n\
+                     middle - Keep the beginning and the end\n\
+                     summarize - Summarize the input with the model first",
+        default_value = "middle"
+    )]
+    pub truncate: TruncateStrategy,
+
+    #[arg(
+        long,
+        help = "Split oversized input into chunks and merge the answers",
+        long_help = "When piped input exceeds the context window, ask the question about each chunk \
+                     concurrently and merge the partial answers with a final request, instead of truncating."
+    )]
+    pub chunked: bool,
+
+    #[arg(
+        long,
+        help = "Answer with compact JSON on stdout",
+        long_help = "Request a JSON answer and print it as compact JSON on stdout, \
+                     without spinners or streaming, for use in scripts."
+    )]
+    pub json: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "JSON schema the answer must match (implies --json)",
+        long_help = "Path to a JSON schema. The answer is validated against it locally and the \
+                     request is retried with the validation errors when it does not match. Implies --json."
+    )]
+    pub schema: Option<PathBuf>,
+
+    #[arg(
+        short = 'o',
+        long,
+        help = "Output format: text, json or ndjson",
+        long_help = "Set the output format:\n\
+                     text - Print the answer as plain text\n\
+                     json - Print one JSON envelope with the answer, model, finish reason, usage and latency\n\
+                     ndjson - Print one JSON event per line: delta, reasoning, usage, done and error",
+        default_value = "text"
+    )]
+    pub output: OutputFormat,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["json", "schema", "cmd", "compare"],
+        help = "Print only the fenced code blocks of the answer"
+    )]
+    pub code: bool,
+
+    #[arg(
+        long,
+        value_name = "LANG|N",
+        conflicts_with_all = ["json", "schema", "cmd", "compare"],
+        help = "Print only the code blocks in this language, or the Nth block (implies --code)"
+    )]
+    pub extract: Option<Selector>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["json", "schema", "cmd", "compare"],
+        help = "Save the code blocks of the answer as files in DIR"
+    )]
+    pub save_code: Option<PathBuf>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["json", "schema", "cmd", "compare"],
+        help = "Copy the code blocks (or the whole answer) to the clipboard",
+        long_help = "Copy the selected code blocks to the clipboard, or the whole answer when it has none. \
+                     Uses the OSC 52 terminal escape sequence, so it also works over SSH."
+    )]
+    pub copy: bool,
+
+    #[arg(
+        long,
+        value_name = "JSON",
+        value_parser = parse_json_object,
+        help = "JSON object merged into the request body, can be repeated",
+        long_help = "JSON object deep merged into the top level of the request body, after `extra_body` \
+                     from the configuration. Use it for provider specific parameters such as \
+                     '{\"top_k\": 40}'. A null value removes a field. Can be repeated."
+    )]
+    pub extra: Vec<serde_json::Value>,
+
+    #[arg(long, help = "Do not read or write the response cache")]
+    pub no_cache: bool,
+
+    #[arg(
+        long,
+        conflicts_with = "no_cache",
+        help = "Ignore the cached answer and store a fresh one"
+    )]
+    pub refresh: bool,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        add = ArgValueCompleter::new(complete::roles),
+        help = "Use a role from the configuration: its system prompt, model, temperature and output"
+    )]
+    pub role: Option<String>,
+
+    #[arg(
+        short = 't',
+        long,
+        value_name = "NAME",
+        conflicts_with = "chunked",
+        add = ArgValueCompleter::new(complete::templates),
+        help = "Build the prompt from a named template",
+        long_help = "Build the prompt from a template in `templates` of the configuration or in \
+                     ~/.config/ask/templates. Placeholders: {{stdin}}, {{input}}, {{file:path}}, \
+                     {{env:VAR}} and {{shell:command}}. The question is optional with a template."
+    )]
+    pub template: Option<String>,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["template", "chunked"],
+        help = "Explain the last failed shell command recorded by the shell integration"
+    )]
+    pub explain_last: bool,
+
+    #[arg(
+        long,
+        conflicts_with_all = ["json", "schema", "template", "explain_last", "compare"],
+        help = "Print only a shell command for the request, without spinner or streaming"
+    )]
+    pub cmd: bool,
+
+    #[arg(
+        short = 'e',
+        long,
+        conflicts_with = "explain_last",
+        help = "Write the question in $EDITOR, also done when no question is given",
+        long_help = "Write the question in $VISUAL or $EDITOR, pre-filled with the question if one is given. \
+                     This also happens when no question is given and stdin is a terminal."
+    )]
+    pub editor: bool,
+
+    #[arg(
+        long,
+        value_name = "LABEL",
+        conflicts_with = "template",
+        help = "Heading of the piped input in the prompt, e.g. \"compiler output\"",
+        long_help = "Heading of the piped input in the prompt, instead of the generic STDIN, \
+                     so the model knows what it is looking at, e.g. \"compiler output\". Not available with \
+                     --template, which places the input itself, nor with a `-` word."
+    )]
+    pub stdin_label: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "MODELS",
+        value_delimiter = ',',
+        add = ArgValueCompleter::new(complete::models),
+        conflicts_with_all = ["json", "schema"],
+        help = "Ask several comma separated models concurrently and show the answers side by side"
+    )]
+    pub compare: Vec<String>,
+
+    /// The question, quotes are optional. A `-` word is replaced with the piped input
+    #[arg(value_name = "QUESTION")]
+    pub input: Vec<String>,
+}
+
+impl ChatArgs {
+    /// The options of the question. `output` is left to the configuration
+    /// unless `-o` was given on the command line.
+    pub fn options(&self, output_given: bool) -> AskOptions {
+        let mode = if self.cmd {
+            Mode::Command
+        } else if !self.compare.is_empty() {
+            Mode::Compare(self.compare.clone())
+        } else if self.json || self.schema.is_some() {
+            Mode::Json(self.schema.clone())
+        } else {
+            Mode::Chat
+        };
+        AskOptions {
+            request: RequestOptions {
+                role: self.role.clone(),
+                reasoning: self.reasoning,
+                extra: self.extra.clone(),
+            },
+            mode,
+            verbose: self.verbose,
+            output: output_given.then_some(self.output),
+            code: self.code_options(),
+            no_cache: self.no_cache,
+            refresh: self.refresh,
+            truncate: self.truncate,
+            chunked: self.chunked,
+            stdin_label: self.stdin_label.clone(),
+            template: self.template.clone(),
+        }
+    }
+
+    fn code_options(&self) -> Option<CodeOptions> {
+        let print_only = self.code || self.extract.is_some();
+        if !print_only && self.save_code.is_none() && !self.copy {
+            return None;
+        }
+        Some(CodeOptions {
+            print_only,
+            selector: self.extract.clone().unwrap_or(Selector::All),
+            save_dir: self.save_code.clone(),
+            copy: self.copy,
+        })
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Ask a question, the same as `ask <QUESTION>`
+    Chat(ChatArgs),
+    /// Create or update the configuration file
+    Config,
+    /// Manage the local response cache
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print the shell completion script
+    Completions {
+        /// bash, elvish, fish, powershell or zsh
+        #[arg(value_parser = ["bash", "elvish", "fish", "powershell", "zsh"])]
+        shell: String,
+    },
+    /// Write commit messages and review diffs
+    Git {
+        #[command(flatten)]
+        options: GitArgs,
+        #[command(subcommand)]
+        action: GitAction,
+    },
+    /// Work with the models offered by the provider
+    Models {
+        #[command(subcommand)]
+        action: ModelsAction,
+    },
+    /// Print the shell integration script: last command recording and a Ctrl-G widget
+    ShellInit {
+        /// bash, zsh or fish
+        shell: Shell,
+    },
+    /// Report token usage and estimated spend from the usage ledger
+    Usage {
+        /// How far back to report, e.g. 24h, 7d or 4weeks
+        #[arg(long, default_value = "30d", value_parser = humantime::parse_duration)]
+        since: Duration,
+        /// Group by model, provider or day
+        #[arg(long, default_value = "model")]
+        by: GroupBy,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// Show the number and size of cached answers
+    Stats,
+    /// Delete all cached answers
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ModelsAction {
+    /// List the available models with their owner and context length
+    List {
+        /// Only show models whose id contains this text
+        #[arg(add = ArgValueCompleter::new(complete::models))]
+        filter: Option<String>,
+        /// Only show models owned by this organization
+        #[arg(long)]
+        owner: Option<String>,
+    },
+}
+
+/// The options of a question that apply to `ask git`, before or after the
+/// action.
+#[derive(Args, Debug)]
+pub struct GitArgs {
+    #[arg(
+        short = 'r',
+        long,
+        global = true,
+        help = "Reasoning effort level (0-3).",
+        default_value = "0"
+    )]
+    pub reasoning: ReasoningEffort,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "JSON",
+        value_parser = parse_json_object,
+        help = "JSON object merged into the request body, can be repeated"
+    )]
+    pub extra: Vec<serde_json::Value>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        add = ArgValueCompleter::new(complete::roles),
+        help = "Use a role from the configuration: its system prompt, model and temperature"
+    )]
+    pub role: Option<String>,
+}
+
+impl From<&GitArgs> for RequestOptions {
+    fn from(args: &GitArgs) -> Self {
+        RequestOptions {
+            role: args.role.clone(),
+            reasoning: args.reasoning,
+            extra: args.extra.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GitAction {
+    /// Write a commit message for the staged changes and commit them
+    Commit {
+        /// Commit without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Review a diff file by file, by default the uncommitted changes
+    Review {
+        /// Revision or range passed to `git diff`, e.g. main..HEAD
+        range: Option<String>,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_args(words: &[&str]) -> ChatArgs {
+        Cli::try_parse_from(std::iter::once("ask").chain(words.iter().copied()))
+            .unwrap()
+            .chat
+    }
+
+    #[test]
+    fn picks_the_mode_and_leaves_output_to_the_config() {
+        let options = chat_args(&["--schema", "s.json", "-o", "json", "q"]).options(false);
+        assert_eq!(options.mode, Mode::Json(Some(PathBuf::from("s.json"))));
+        assert_eq!(options.output, None);
+        assert!(options.code.is_none());
+
+        let options = chat_args(&["--compare", "a,b", "-o", "json", "q"]).options(true);
+        assert_eq!(options.mode, Mode::Compare(vec!["a".into(), "b".into()]));
+        assert_eq!(options.output, Some(OutputFormat::Json));
+
+        let options = chat_args(&["--extract", "rust", "q"]).options(false);
+        assert_eq!(options.mode, Mode::Chat);
+        assert!(options.code.is_some_and(|code| code.print_only));
+    }
+
+    #[test]
+    fn code_options_conflict_with_other_modes() {
+        let parse = |words: &[&str]| {
+            Cli::try_parse_from(std::iter::once("ask").chain(words.iter().copied()))
+        };
+        assert!(parse(&["--code", "--copy", "q"]).is_ok());
+        assert!(parse(&["--json", "--code", "q"]).is_err());
+        assert!(parse(&["--schema", "s.json", "--copy", "q"]).is_err());
+        assert!(parse(&["--compare", "a,b", "--extract", "rust", "q"]).is_err());
+    }
+
+    #[test]
+    fn options_do_not_mix_with_subcommands() {
+        let parse = |words: &[&str]| {
+            Cli::try_parse_from(std::iter::once("ask").chain(words.iter().copied()))
+        };
+        assert_eq!(
+            parse(&["-v", "chat", "q"]).unwrap().misplaced_subcommand(),
+            Some("chat")
+        );
+        assert!(parse(&["chat", "-v", "q"]).unwrap().command.is_some());
+        assert!(parse(&["-v", "chat about it"])
+            .unwrap()
+            .misplaced_subcommand()
+            .is_none());
+
+        for words in [
+            ["git", "--role", "x", "commit"],
+            ["git", "commit", "--role", "x"],
+        ] {
+            let Some(Command::Git { options, .. }) = parse(&words).unwrap().command else {
+                panic!("expected `ask git`");
+            };
+            assert_eq!(options.role.as_deref(), Some("x"));
+        }
+    }
 }
@@ -0,0 +1,34 @@
+use clap_complete::engine::CompletionCandidate;
+use std::ffi::OsStr;
+
+use ask::config::setup::Config;
+use ask::config::templates;
+use ask::services::cache;
+
+/// Configuration for completion, which must not fail or prompt.
+fn config() -> Config {
+    Config::load().unwrap_or_default()
+}
+
+fn matching(names: impl IntoIterator<Item = String>, current: &OsStr) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    names
+        .into_iter()
+        .filter(|name| name.starts_with(current.as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+pub fn roles(current: &OsStr) -> Vec<CompletionCandidate> {
+    matching(config().roles.into_keys(), current)
+}
+
+pub fn templates(current: &OsStr) -> Vec<CompletionCandidate> {
+    matching(templates::names(&config()), current)
+}
+
+/// Model ids from the cache written by `ask models list`, so completing does
+/// not wait for the network.
+pub fn models(current: &OsStr) -> Vec<CompletionCandidate> {
+    matching(cache::cached_models(), current)
+}
@@ -1,3 +1,4 @@
 mod args;
+mod complete;
 
-pub use args::Cli;
+pub use args::{CacheAction, ChatArgs, Cli, Command, GitAction, ModelsAction};
@@ -0,0 +1,155 @@
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::setup::Config;
+use crate::error::AskError;
+use crate::services::api::{self, Completion, Notify};
+use crate::services::client::build_client;
+use crate::services::ledger;
+use crate::services::request::ReasoningEffort;
+use crate::services::schema::APIResponse;
+use crate::services::stream::{events, Event, EventStream};
+
+/// Async client for the provider of an `ask` configuration: its `base_url`,
+/// models, retries, `http` settings, `temperature` and `extra_body`. Usage is
+/// recorded in the usage ledger when `usage_ledger` is set, like answers of the
+/// command line. Nothing is printed, see [`Client::on_notice`].
+pub struct Client {
+    config: Config,
+    api_key: String,
+    http: reqwest::Client,
+    model: Option<String>,
+    reasoning: ReasoningEffort,
+    extra: Vec<Value>,
+    notify: Arc<Notify>,
+}
+
+impl Client {
+    /// Client for the configuration file of the command line with `role`, or
+    /// else `default_role`, applied. The API key is read from `ASK_API_KEY`.
+    pub fn load(role: Option<&str>) -> Result<Self, AskError> {
+        Self::new(Config::load_with_role(role)?, api::load_api_key()?)
+    }
+
+    /// Client for `config`, whose `base_url` selects the provider.
+    pub fn new(config: Config, api_key: impl Into<String>) -> Result<Self, AskError> {
+        Ok(Self {
+            http: build_client(&config.http)?,
+            extra: api::config_extra(&config),
+            api_key: api_key.into(),
+            model: None,
+            reasoning: ReasoningEffort::None,
+            notify: Arc::new(|_| {}),
+            config,
+        })
+    }
+
+    /// Uses `model` instead of `model` or `thinking_model` of the configuration.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Sets the reasoning effort. Like `ask -r`, any effort switches to
+    /// `thinking_model` unless a model was set with [`Client::model`].
+    pub fn reasoning(mut self, reasoning: ReasoningEffort) -> Self {
+        self.reasoning = reasoning;
+        self
+    }
+
+    /// Deep merges `extra` into every request body, like `ask --extra`.
+    pub fn extra(mut self, extra: &Value) -> Self {
+        self.extra.push(extra.clone());
+        self
+    }
+
+    /// Records the usage of requests in the usage ledger of the command line,
+    /// overriding `usage_ledger` of the configuration.
+    pub fn usage_ledger(mut self, enabled: bool) -> Self {
+        self.config.usage_ledger = enabled;
+        self
+    }
+
+    /// Calls `notice` with messages the command line prints to stderr, like
+    /// retries and failures to write the usage ledger. Ignored by default.
+    pub fn on_notice(mut self, notice: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        self.notify = Arc::new(notice);
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The model requests are sent to.
+    pub fn selected_model(&self) -> String {
+        self.model
+            .clone()
+            .unwrap_or_else(|| api::select_model(&self.config, &self.reasoning))
+    }
+
+    /// Sends `prompt` as a user message and waits for the whole answer.
+    pub async fn chat(&self, prompt: &str) -> Result<Completion, AskError> {
+        let body = api::build_request_body(
+            self.selected_model(),
+            prompt.to_string(),
+            false,
+            self.reasoning,
+            false,
+            &self.extra,
+        )?;
+        api::complete(
+            &self.http,
+            &self.config,
+            &self.api_key,
+            body,
+            self.notify.as_ref(),
+        )
+        .await
+    }
+
+    /// Sends `prompt` as a user message and streams the answer. Fails before
+    /// the first event when the request is rejected.
+    pub async fn stream(&self, prompt: &str) -> Result<EventStream, AskError> {
+        let model = self.selected_model();
+        let body = api::build_request_body(
+            model.clone(),
+            prompt.to_string(),
+            true,
+            self.reasoning,
+            true,
+            &self.extra,
+        )?;
+        let response = api::send_request(
+            &self.http,
+            &self.config,
+            &self.api_key,
+            body,
+            self.notify.as_ref(),
+        )
+        .await?;
+        let idle_timeout = Duration::from_secs(self.config.http.stream_idle_timeout_secs);
+
+        let (config, notify) = (self.config.clone(), self.notify.clone());
+        Ok(events(response, idle_timeout)
+            .inspect(move |event| {
+                if let Ok(Event::Usage(usage)) = event {
+                    ledger::record_with(&config, &model, usage, notify.as_ref());
+                }
+            })
+            .boxed())
+    }
+
+    /// Models offered by the provider.
+    pub async fn models(&self) -> Result<APIResponse, AskError> {
+        api::list_models(
+            &self.http,
+            &self.config,
+            &self.api_key,
+            self.notify.as_ref(),
+        )
+        .await
+    }
+}
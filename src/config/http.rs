@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Settings for the HTTP client, for networks behind proxies or private CAs.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct HttpConfig {
+    /// Proxy URL for all requests, e.g. `http://proxy.corp:3128`. Without it
+    /// the usual `HTTPS_PROXY`/`HTTP_PROXY` variables are used.
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Comma separated hosts that bypass `proxy`
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+
+    /// PEM bundle of extra root certificates to trust
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// PEM client certificate for mutual TLS, used with `client_key`
+    #[serde(default)]
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM (PKCS#8) private key of `client_cert`
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
+
+    #[serde(default = "HttpConfig::default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+
+    /// Maximum wait for any single read from the connection. Unset by default
+    /// since a reasoning model can stay silent for minutes before answering,
+    /// streamed answers are covered by `stream_idle_timeout_secs`.
+    #[serde(default)]
+    pub read_timeout_secs: Option<u64>,
+
+    /// Limit for a whole request including the response body. Unset by
+    /// default since long streamed answers can take minutes.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Aborts a streamed answer when no data arrives for this long
+    #[serde(default = "HttpConfig::default_stream_idle_timeout_secs")]
+    pub stream_idle_timeout_secs: u64,
+}
+
+impl HttpConfig {
+    fn default_connect_timeout_secs() -> u64 {
+        10
+    }
+
+    fn default_stream_idle_timeout_secs() -> u64 {
+        60
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+            connect_timeout_secs: Self::default_connect_timeout_secs(),
+            read_timeout_secs: None,
+            timeout_secs: None,
+            stream_idle_timeout_secs: Self::default_stream_idle_timeout_secs(),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::env;
+
+use crate::error::AskError;
+
+/// Replaces `${VAR}` with the value of the environment variable `VAR`, so
+/// secrets can stay out of the configuration file. `$$` is a literal `$`.
+pub fn interpolate_env(value: &str) -> Result<String, AskError> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after_dollar) = after.strip_prefix('$') {
+            result.push('$');
+            rest = after_dollar;
+        } else if let Some(braced) = after.strip_prefix('{') {
+            let end = braced.find('}').ok_or_else(|| {
+                AskError::Config(format!("Unclosed ${{ in configuration value: {}", value))
+            })?;
+            let name = &braced[..end];
+            let var = env::var(name).map_err(|_| {
+                AskError::Config(format!("Environment variable {} is not set", name))
+            })?;
+            result.push_str(&var);
+            rest = &braced[end + 1..];
+        } else {
+            result.push('$');
+            rest = after;
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_env_variables() {
+        env::set_var("ASK_TEST_TENANT", "acme");
+        assert_eq!(
+            interpolate_env("tenant-${ASK_TEST_TENANT}/$$5/$x").unwrap(),
+            "tenant-acme/$5/$x"
+        );
+        assert!(interpolate_env("${ASK_TEST_UNSET_VARIABLE}").is_err());
+        assert!(interpolate_env("${ASK_TEST_TENANT").is_err());
+    }
+}
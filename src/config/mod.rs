@@ -1,3 +1,6 @@
+pub mod http;
+pub mod interpolate;
 pub mod setup;
+pub mod templates;
 
 pub use setup::Config;
@@ -1,9 +1,14 @@
-use cliclack::{confirm, input, intro, outro};
+use cliclack::{confirm, input, intro, log, outro, select};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
 
-use crate::services::api::check_models;
+use super::http::HttpConfig;
+use crate::error::AskError;
+use crate::services::api::{self, check_exists};
+use crate::services::output::OutputFormat;
+use crate::services::schema::APIResponse;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Config {
@@ -27,6 +32,158 @@ pub struct Config {
 
     #[serde(default = "Config::default_stream")]
     pub stream: bool,
+
+    /// Context window in tokens per model, matched by exact name or prefix
+    #[serde(default = "Config::default_context_sizes")]
+    pub context_sizes: HashMap<String, usize>,
+
+    /// Context window used when the model is not in `context_sizes`
+    #[serde(default = "Config::default_context_size")]
+    pub default_context_size: usize,
+
+    /// Maximum number of requests in flight in chunked mode
+    #[serde(default = "Config::default_max_concurrency")]
+    pub max_concurrency: usize,
+
+    /// Most chunks `--chunked` or `--truncate summarize` may send, one paid
+    /// request each
+    #[serde(default = "Config::default_max_chunks")]
+    pub max_chunks: usize,
+
+    /// Retries for rate limits, server errors and connection failures
+    #[serde(default = "Config::default_max_retries")]
+    pub max_retries: u32,
+
+    /// First retry delay, doubled on every further attempt
+    #[serde(default = "Config::default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+
+    /// Upper bound for a single retry delay, including `Retry-After`
+    #[serde(default = "Config::default_retry_max_delay_ms")]
+    pub retry_max_delay_ms: u64,
+
+    /// Extra headers sent with every request, values support `${ENV}`
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// Extra query parameters sent with every request, values support `${ENV}`
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+
+    /// Reuse stored answers for identical requests
+    #[serde(default = "Config::default_cache")]
+    pub cache: bool,
+
+    /// How long a stored answer is reused
+    #[serde(default = "Config::default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+
+    /// Provider specific fields deep merged into every request body
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub extra_body: serde_json::Value,
+
+    /// Proxy, TLS and timeout settings
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Append the token usage of every request to the usage ledger
+    #[serde(default = "Config::default_usage_ledger")]
+    pub usage_ledger: bool,
+
+    /// Price per model, matched by exact name or prefix like `context_sizes`
+    #[serde(default = "Config::default_prices")]
+    pub prices: HashMap<String, Price>,
+
+    /// Sampling temperature, left to the provider when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+
+    /// Output format used when `--output` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+
+    /// Named presets selected with `--role`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub roles: HashMap<String, Role>,
+
+    /// Other providers for `--compare`, used with `name:model` entries
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, Profile>,
+
+    /// Role used when `--role` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_role: Option<String>,
+
+    /// Prompt templates by name, used with `-t`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub templates: HashMap<String, String>,
+
+    /// Warn once the estimated spend of the current month reaches this amount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_budget: Option<f64>,
+}
+
+/// A persona bundling a system prompt, model and parameters. Fields that are
+/// not set keep the value of the configuration.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Role {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Replaces both `model` and `thinking_model`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputFormat>,
+}
+
+/// Another provider, selected in `--compare` with `name:model`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Profile {
+    pub base_url: String,
+    /// Environment variable holding its API key, none for local servers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+}
+
+impl Profile {
+    /// Its API key, empty without `api_key_env`.
+    pub fn api_key(&self) -> Result<String, AskError> {
+        let Some(name) = &self.api_key_env else {
+            return Ok(String::new());
+        };
+        env::var(name).map_err(|_| {
+            AskError::Auth(format!(
+                "{} environment variable, the API key for {}, is not set",
+                name, self.base_url
+            ))
+        })
+    }
+}
+
+/// Price in dollars per million tokens.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl Price {
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input + completion_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Value of the exact key `model`, or else of its longest matching prefix.
+fn lookup_by_prefix<'a, V>(table: &'a HashMap<String, V>, model: &str) -> Option<&'a V> {
+    table.get(model).or_else(|| {
+        table
+            .iter()
+            .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, value)| value)
+    })
 }
 
 impl Config {
@@ -54,14 +211,141 @@ impl Config {
         true
     }
 
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+    fn default_context_sizes() -> HashMap<String, usize> {
+        [
+            ("gemini-2.0-flash", 1_048_576),
+            ("gemini-2.5", 1_048_576),
+            ("gpt-4o", 128_000),
+            ("gpt-4.1", 1_047_576),
+            ("gpt-3.5-turbo", 16_385),
+            ("claude", 200_000),
+        ]
+        .into_iter()
+        .map(|(model, size)| (model.to_string(), size))
+        .collect()
+    }
+
+    fn default_context_size() -> usize {
+        8_192
+    }
+
+    fn default_max_concurrency() -> usize {
+        4
+    }
+
+    fn default_max_chunks() -> usize {
+        20
+    }
+
+    fn default_max_retries() -> u32 {
+        3
+    }
+
+    fn default_retry_base_delay_ms() -> u64 {
+        1_000
+    }
+
+    fn default_retry_max_delay_ms() -> u64 {
+        30_000
+    }
+
+    fn default_cache() -> bool {
+        false
+    }
+
+    fn default_cache_ttl_secs() -> u64 {
+        86_400
+    }
+
+    fn default_usage_ledger() -> bool {
+        true
+    }
+
+    fn default_prices() -> HashMap<String, Price> {
+        [
+            ("gemini-2.0-flash", 0.10, 0.40),
+            ("gemini-2.5-flash", 0.30, 2.50),
+            ("gemini-2.5-pro", 1.25, 10.00),
+            ("gpt-4o-mini", 0.15, 0.60),
+            ("gpt-4o", 2.50, 10.00),
+            ("gpt-4.1", 2.00, 8.00),
+        ]
+        .into_iter()
+        .map(|(model, input, output)| (model.to_string(), Price { input, output }))
+        .collect()
+    }
+
+    /// Looks up the context window of `model`, preferring an exact match and
+    /// then the longest matching prefix.
+    /// The profile and model of a `name:model` entry of `--compare`, `None`
+    /// when `name` is not a profile, e.g. for the model id `llama3:8b`.
+    pub fn profile<'a>(&self, entry: &'a str) -> Option<(&Profile, &'a str)> {
+        let (name, model) = entry.split_once(':')?;
+        Some((self.profiles.get(name)?, model))
+    }
+
+    /// This configuration sending to `profile` instead. Headers, query
+    /// parameters and `extra_body` are meant for `base_url`, so they are not
+    /// sent along.
+    pub fn with_profile(&self, profile: &Profile) -> Self {
+        Self {
+            base_url: profile.base_url.clone(),
+            headers: HashMap::new(),
+            query: HashMap::new(),
+            extra_body: serde_json::Value::Null,
+            ..self.clone()
+        }
+    }
+
+    pub fn context_size(&self, model: &str) -> usize {
+        lookup_by_prefix(&self.context_sizes, model)
+            .copied()
+            .unwrap_or(self.default_context_size)
+    }
+
+    /// Price of `model`, looked up the same way as the context window.
+    pub fn price(&self, model: &str) -> Option<Price> {
+        lookup_by_prefix(&self.prices, model).copied()
+    }
+
+    pub fn load() -> Result<Self, AskError> {
         let path = get_askconfig_path();
-        let config_str = std::fs::read_to_string(&path)?;
-        let config = serde_json::from_str(&config_str)?;
-        Ok(config)
+        let config_str = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AskError::ConfigMissing(path.clone()),
+            _ => AskError::Io(e),
+        })?;
+        serde_json::from_str(&config_str)
+            .map_err(|e| AskError::Config(format!("{}: {}", path.display(), e)))
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Loads the configuration with the role `name`, or the default role,
+    /// applied on top.
+    pub fn load_with_role(name: Option<&str>) -> Result<Self, AskError> {
+        Self::load()?.with_role(name)
+    }
+
+    pub fn with_role(mut self, name: Option<&str>) -> Result<Self, AskError> {
+        let Some(name) = name.map(str::to_string).or(self.default_role.clone()) else {
+            return Ok(self);
+        };
+        let role =
+            self.roles.get(&name).cloned().ok_or_else(|| {
+                AskError::Usage(format!("Role {} is not defined in `roles`", name))
+            })?;
+        if let Some(system_prompt) = role.system_prompt {
+            self.system_prompt = system_prompt;
+        }
+        // The role picks the model whatever the reasoning effort
+        if let Some(model) = role.model {
+            self.thinking_model = model.clone();
+            self.model = model;
+        }
+        self.temperature = role.temperature.or(self.temperature);
+        self.output = role.output.or(self.output);
+        Ok(self)
+    }
+
+    pub fn save(&self) -> Result<(), AskError> {
         let path = get_askconfig_path();
         let file = std::fs::File::create(&path)?;
         serde_json::to_writer(file, self)?;
@@ -79,6 +363,28 @@ impl Default for Config {
             system_prompt: Self::default_system_prompt(),
             system_role: Self::default_system_role(),
             stream: Self::default_stream(),
+            context_sizes: Self::default_context_sizes(),
+            default_context_size: Self::default_context_size(),
+            max_concurrency: Self::default_max_concurrency(),
+            max_chunks: Self::default_max_chunks(),
+            max_retries: Self::default_max_retries(),
+            retry_base_delay_ms: Self::default_retry_base_delay_ms(),
+            retry_max_delay_ms: Self::default_retry_max_delay_ms(),
+            headers: HashMap::new(),
+            query: HashMap::new(),
+            cache: Self::default_cache(),
+            cache_ttl_secs: Self::default_cache_ttl_secs(),
+            extra_body: serde_json::Value::Null,
+            http: HttpConfig::default(),
+            usage_ledger: Self::default_usage_ledger(),
+            prices: Self::default_prices(),
+            monthly_budget: None,
+            templates: HashMap::new(),
+            temperature: None,
+            output: None,
+            roles: HashMap::new(),
+            profiles: HashMap::new(),
+            default_role: None,
         }
     }
 }
@@ -104,7 +410,7 @@ fn load_existing_config() -> Config {
         .unwrap_or_default()
 }
 
-fn prompt_base_url(current: &str) -> Result<String, Box<dyn std::error::Error>> {
+fn prompt_base_url(current: &str) -> Result<String, AskError> {
     Ok(input("What is the base_url?")
         .default_input(current)
         .validate(|input: &String| {
@@ -119,32 +425,70 @@ fn prompt_base_url(current: &str) -> Result<String, Box<dyn std::error::Error>>
         .interact()?)
 }
 
-fn prompt_string(prompt: &str, current: &str) -> Result<String, Box<dyn std::error::Error>> {
+fn prompt_string(prompt: &str, current: &str) -> Result<String, AskError> {
     Ok(input(prompt).default_input(current).interact()?)
 }
 
-fn prompt_bool(prompt: &str, default: bool) -> Result<bool, Box<dyn std::error::Error>> {
+fn prompt_bool(prompt: &str, default: bool) -> Result<bool, AskError> {
     Ok(confirm(prompt).initial_value(default).interact()?)
 }
 
-async fn validate_model_if_requested(
-    base_url: &str,
-    model: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let skip_validate = prompt_bool(
-        "Do you want to skip model validation? This defaults to true due to the different formats of the models endpoint.",
-        true
-    )?;
+/// Lists the provider's models for the pickers, or `None` when they cannot
+/// be listed and have to be entered by name.
+async fn fetch_models(config: &Config) -> Option<APIResponse> {
+    let api_key = env::var("ASK_API_KEY").ok()?;
+    match api::fetch_models(config, &api_key).await {
+        Ok(models) if !models.data.is_empty() => Some(models),
+        Ok(_) => None,
+        Err(e) => {
+            let _ = log::warning(format!("Could not list the models: {}", e));
+            None
+        }
+    }
+}
 
-    if !skip_validate {
-        let api_key = env::var("ASK_API_KEY")?;
-        check_models(base_url, &api_key, model).await?;
+/// Picks a model from the list, or asks for its name when there is no list
+/// or "Other" is chosen.
+fn prompt_model(
+    prompt: &str,
+    current: &str,
+    models: Option<&APIResponse>,
+) -> Result<String, AskError> {
+    let Some(models) = models else {
+        return prompt_string(prompt, current);
+    };
+
+    let mut picker = select(prompt).filter_mode().max_rows(12);
+    for model in &models.data {
+        let hint = [
+            model.owned_by.clone(),
+            model
+                .context_length
+                .map(|tokens| format!("{} tokens", tokens)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(", ");
+        picker = picker.item(model.id.clone(), &model.id, hint);
+    }
+    picker = picker.item(String::new(), "Other", "enter a model name");
+    if check_exists(current, models) {
+        picker = picker.initial_value(current.to_string());
     }
 
-    Ok(())
+    let choice = picker.interact()?;
+    if !choice.is_empty() {
+        return Ok(choice);
+    }
+    let model = prompt_string(prompt, current)?;
+    if !model.is_empty() && !check_exists(&model, models) {
+        log::warning(format!("{} is not in the provider's model list", model))?;
+    }
+    Ok(model)
 }
 
-pub async fn configure() -> Result<(), Box<dyn std::error::Error>> {
+pub async fn configure() -> Result<(), AskError> {
     intro("Welcome to the configuration mode")?;
 
     let current_config = load_existing_config();
@@ -153,13 +497,20 @@ pub async fn configure() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let base_url = prompt_base_url(&current_config.base_url)?;
-    let model = prompt_string(
+    let models = fetch_models(&Config {
+        base_url: base_url.clone(),
+        ..current_config.clone()
+    })
+    .await;
+    let model = prompt_model(
         "What model do you want to use? Smaller models are recommended: ",
         &current_config.model,
+        models.as_ref(),
     )?;
-    let thinking_model = prompt_string(
+    let thinking_model = prompt_model(
         "What is the thinking model? (leave empty if you don't want to use thinking) ",
         &current_config.thinking_model,
+        models.as_ref(),
     )?;
     let system_prompt =
         prompt_string("What is the system prompt? ", &current_config.system_prompt)?;
@@ -170,8 +521,6 @@ pub async fn configure() -> Result<(), Box<dyn std::error::Error>> {
     )?;
     let stream = prompt_bool("Do you want to enable streaming?", current_config.stream)?;
 
-    validate_model_if_requested(&base_url, &model).await?;
-
     let new_config = Config {
         base_url,
         legacy_completions,
@@ -180,9 +529,88 @@ pub async fn configure() -> Result<(), Box<dyn std::error::Error>> {
         system_prompt,
         system_role,
         stream,
+        ..current_config
     };
 
     new_config.save()?;
     outro("Configuration complete")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_size_prefers_longest_prefix() {
+        let mut config = Config::default();
+        config
+            .context_sizes
+            .insert("gpt-4o-mini".to_string(), 64_000);
+
+        assert_eq!(config.context_size("gpt-4o-mini-2024-07-18"), 64_000);
+        assert_eq!(config.context_size("gpt-4o-2024-08-06"), 128_000);
+        assert_eq!(config.context_size("unknown"), config.default_context_size);
+    }
+
+    #[test]
+    fn role_overrides_configuration() {
+        let mut config = Config {
+            temperature: Some(0.7),
+            ..Default::default()
+        };
+        config.roles.insert(
+            "translator".to_string(),
+            Role {
+                system_prompt: Some("Translate to French.".to_string()),
+                model: Some("gpt-4o-mini".to_string()),
+                output: Some(OutputFormat::Json),
+                ..Default::default()
+            },
+        );
+        config.default_role = Some("translator".to_string());
+
+        let with_default = config.clone().with_role(None).unwrap();
+        assert_eq!(with_default.system_prompt, "Translate to French.");
+        assert_eq!(with_default.model, "gpt-4o-mini");
+        assert_eq!(with_default.thinking_model, "gpt-4o-mini");
+        assert_eq!(with_default.temperature, Some(0.7));
+        assert_eq!(with_default.output, Some(OutputFormat::Json));
+
+        assert!(config.with_role(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn routes_profile_entries() {
+        let mut config = Config {
+            query: HashMap::from([("tenant".to_string(), "a".to_string())]),
+            ..Default::default()
+        };
+        config.profiles.insert(
+            "local".to_string(),
+            Profile {
+                base_url: "http://localhost:11434/v1/".to_string(),
+                api_key_env: None,
+            },
+        );
+
+        let (profile, model) = config.profile("local:llama3:8b").unwrap();
+        assert_eq!(model, "llama3:8b");
+        assert_eq!(profile.api_key().unwrap(), "");
+        let local = config.with_profile(profile);
+        assert_eq!(local.base_url, "http://localhost:11434/v1/");
+        assert!(local.query.is_empty());
+
+        assert!(config.profile("llama3:8b").is_none());
+        assert!(config.profile("gpt-4o-mini").is_none());
+    }
+
+    #[test]
+    fn price_uses_prefix_and_costs_per_million() {
+        let config = Config::default();
+        let price = config.price("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(price.input, 0.15);
+        assert!((price.cost(1_000_000, 500_000) - 0.45).abs() < 1e-9);
+        assert_eq!(config.price("unknown"), None);
+    }
+}
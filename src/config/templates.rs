@@ -0,0 +1,61 @@
+use std::env;
+use std::path::PathBuf;
+
+use super::setup::Config;
+use crate::error::AskError;
+
+/// Extensions tried, in order, for a template file in the templates directory.
+const EXTENSIONS: [&str; 3] = ["md", "txt", ""];
+
+pub fn get_templates_dir() -> PathBuf {
+    if let Ok(dir) = env::var("ASK_TEMPLATES_DIR") {
+        return PathBuf::from(dir);
+    }
+    env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("ask").join("templates"))
+        .unwrap_or_else(|_| PathBuf::from("./templates"))
+}
+
+/// Text of the template `name`, from `templates` in the configuration or else
+/// from a file named `name`, `name.md` or `name.txt` in the templates directory.
+pub fn load(config: &Config, name: &str) -> Result<String, AskError> {
+    if let Some(template) = config.templates.get(name) {
+        return Ok(template.clone());
+    }
+    let dir = get_templates_dir();
+    EXTENSIONS
+        .iter()
+        // Appended, so a dot in the name like `review.v2` is kept
+        .map(|extension| match *extension {
+            "" => dir.join(name),
+            extension => dir.join(format!("{}.{}", name, extension)),
+        })
+        .find(|path| path.is_file())
+        .map(|path| std::fs::read_to_string(path).map_err(AskError::from))
+        .unwrap_or_else(|| {
+            Err(AskError::Usage(format!(
+                "Template {} not found in the configuration or in {}",
+                name,
+                dir.display()
+            )))
+        })
+}
+
+/// Names of all templates, sorted and without duplicates.
+pub fn names(config: &Config) -> Vec<String> {
+    let mut names: Vec<String> = config.templates.keys().cloned().collect();
+    if let Ok(entries) = std::fs::read_dir(get_templates_dir()) {
+        names.extend(entries.flatten().filter_map(|entry| {
+            let path = entry.path();
+            if !path.is_file() {
+                return None;
+            }
+            path.file_stem()?.to_str().map(str::to_string)
+        }));
+    }
+    names.sort();
+    names.dedup();
+    names
+}
@@ -0,0 +1,170 @@
+use reqwest::StatusCode;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::services::api_error::ApiError;
+
+/// Exit codes, stable so wrapper scripts can react to the category of a failure.
+pub mod exit_code {
+    /// Anything not covered below
+    pub const GENERAL: i32 = 1;
+    /// Invalid arguments, also used by clap for parse errors
+    pub const USAGE: i32 = 2;
+    /// The configuration file is missing or invalid
+    pub const CONFIG: i32 = 3;
+    /// The API key is missing or was rejected
+    pub const AUTH: i32 = 4;
+    /// The provider could not be reached
+    pub const NETWORK: i32 = 5;
+    /// Rate limit or quota reached, worth retrying later
+    pub const RATE_LIMIT: i32 = 6;
+    /// The provider returned any other error
+    pub const API: i32 = 7;
+    /// The response could not be parsed
+    pub const PARSE: i32 = 8;
+    /// The provider refused to answer because of its content filter
+    pub const CONTENT_FILTERED: i32 = 9;
+    /// The user cancelled a prompt, same as a shell interrupted by SIGINT
+    pub const CANCELLED: i32 = 130;
+}
+
+#[derive(Debug)]
+pub enum AskError {
+    /// The configuration file does not exist
+    ConfigMissing(PathBuf),
+    /// The configuration file or a setting is invalid
+    Config(String),
+    /// The API key is missing or was rejected
+    Auth(String),
+    /// The request could not be sent or the connection dropped
+    Network(reqwest::Error),
+    /// A streamed answer stalled for longer than the idle timeout
+    StreamStalled(Duration),
+    /// The provider answered 429
+    RateLimit(ApiError),
+    /// The provider answered with another error status
+    Api(ApiError),
+    /// The response was not in the expected format
+    Parse(String),
+    /// The user cancelled an interactive prompt
+    Cancelled,
+    /// The answer was stopped by the provider's content filter
+    ContentFiltered,
+    /// Reading or writing a local file failed
+    Io(io::Error),
+    /// Invalid input from the user
+    Usage(String),
+    /// A bug in `ask` rather than a problem with the input or the provider
+    Internal(String),
+}
+
+impl AskError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AskError::ConfigMissing(_) | AskError::Config(_) => exit_code::CONFIG,
+            AskError::Auth(_) => exit_code::AUTH,
+            AskError::Network(_) | AskError::StreamStalled(_) => exit_code::NETWORK,
+            AskError::RateLimit(_) => exit_code::RATE_LIMIT,
+            AskError::Api(_) => exit_code::API,
+            AskError::Parse(_) => exit_code::PARSE,
+            AskError::Cancelled => exit_code::CANCELLED,
+            AskError::ContentFiltered => exit_code::CONTENT_FILTERED,
+            AskError::Usage(_) => exit_code::USAGE,
+            AskError::Io(_) | AskError::Internal(_) => exit_code::GENERAL,
+        }
+    }
+}
+
+impl fmt::Display for AskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AskError::ConfigMissing(path) => write!(
+                f,
+                "Configuration file {} does not exist. Please run `ask config` first.",
+                path.display()
+            ),
+            AskError::Config(message) => write!(f, "Invalid configuration: {}", message),
+            AskError::Auth(message) => write!(f, "{}", message),
+            AskError::Network(e) => write!(f, "Network error: {}", e),
+            AskError::StreamStalled(idle) => write!(
+                f,
+                "No data received for {}s, aborting the stream",
+                idle.as_secs()
+            ),
+            AskError::RateLimit(e) | AskError::Api(e) => write!(f, "{}", e),
+            AskError::Parse(message) => write!(f, "Could not parse the response: {}", message),
+            AskError::Cancelled => write!(f, "Cancelled"),
+            AskError::ContentFiltered => {
+                write!(f, "The provider's content filter stopped the answer")
+            }
+            AskError::Io(e) => write!(f, "{}", e),
+            AskError::Usage(message) => write!(f, "{}", message),
+            AskError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AskError {}
+
+impl From<ApiError> for AskError {
+    fn from(error: ApiError) -> Self {
+        match error.status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => AskError::Auth(error.to_string()),
+            StatusCode::TOO_MANY_REQUESTS => AskError::RateLimit(error),
+            _ => AskError::Api(error),
+        }
+    }
+}
+
+impl From<reqwest::Error> for AskError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_decode() {
+            AskError::Parse(error.to_string())
+        } else {
+            AskError::Network(error)
+        }
+    }
+}
+
+impl From<serde_json::Error> for AskError {
+    fn from(error: serde_json::Error) -> Self {
+        AskError::Parse(error.to_string())
+    }
+}
+
+impl From<io::Error> for AskError {
+    fn from(error: io::Error) -> Self {
+        // cliclack reports Ctrl-C and Esc as an interrupted read
+        if error.kind() == io::ErrorKind::Interrupted {
+            AskError::Cancelled
+        } else {
+            AskError::Io(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_api_status_to_exit_code() {
+        let error: AskError = ApiError::parse(StatusCode::TOO_MANY_REQUESTS, String::new()).into();
+        assert_eq!(error.exit_code(), exit_code::RATE_LIMIT);
+
+        let error: AskError = ApiError::parse(StatusCode::UNAUTHORIZED, String::new()).into();
+        assert_eq!(error.exit_code(), exit_code::AUTH);
+
+        let error: AskError = ApiError::parse(StatusCode::BAD_REQUEST, String::new()).into();
+        assert_eq!(error.exit_code(), exit_code::API);
+    }
+
+    #[test]
+    fn maps_interrupted_prompt_to_cancelled() {
+        let error: AskError = io::Error::new(io::ErrorKind::Interrupted, "ctrl-c").into();
+        assert!(matches!(error, AskError::Cancelled));
+        assert_eq!(error.exit_code(), exit_code::CANCELLED);
+    }
+}
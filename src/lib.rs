@@ -0,0 +1,41 @@
+//! Quick questions to OpenAI compatible chat completions APIs, configured
+//! like the `ask` command line, which is built on this crate.
+//!
+//! ```no_run
+//! use ask::{Client, Event};
+//! use futures_util::StreamExt;
+//!
+//! # async fn run() -> Result<(), ask::AskError> {
+//! // ~/.askconfig with the default role, and ASK_API_KEY
+//! let client = Client::load(None)?;
+//! let answer = client.chat("What is a monad?").await?;
+//! println!("{}", answer.content);
+//!
+//! let mut events = client.model("gpt-4o-mini").stream("And a functor?").await?;
+//! while let Some(event) = events.next().await {
+//!     if let Event::Delta(text) = event? {
+//!         print!("{}", text);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The documented API is [`Client`] with the types it uses. The other modules
+//! are public only for the `ask` binary and may change in any release.
+mod client;
+pub mod config;
+pub mod error;
+
+#[doc(hidden)]
+pub mod models;
+#[doc(hidden)]
+pub mod services;
+
+pub use client::Client;
+pub use config::Config;
+pub use error::AskError;
+pub use services::api::Completion;
+pub use services::request::ReasoningEffort;
+pub use services::schema::{APIResponse, Model, Usage};
+pub use services::stream::{Event, EventStream};
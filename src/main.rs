@@ -1,53 +1,116 @@
-use clap::Parser;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use std::io::{self, Read};
-pub mod cli;
-pub mod config;
-pub mod models;
-pub mod services;
 
-use config::setup as config_setup;
-use config::setup::Config;
+use ask::config::setup as config_setup;
+use ask::error::AskError;
+use ask::services;
+use ask::services::question::{Question, RequestOptions};
+
+mod cli;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = cli::Cli::parse();
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+async fn run() -> Result<(), AskError> {
+    // Answers the shell when it calls back for completions, then exits
+    clap_complete::CompleteEnv::with_factory(cli::Cli::command).complete();
+
+    let matches = cli::Cli::command().get_matches();
+    let args = cli::Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Some(subcommand) = args.misplaced_subcommand() {
+        return Err(AskError::Usage(format!(
+            "Options go after the subcommand, e.g. `ask {} --help`",
+            subcommand
+        )));
+    }
 
     if args.configure {
-        // This is configuration mode
-        config_setup::configure().await?;
-    } else {
-        // check if the configuration file exists
-        if !config_setup::get_askconfig_path().exists() {
-            eprintln!("Configuration file does not exist. Please run the application in configuration mode first.");
-            std::process::exit(1);
-        }
+        eprintln!("Warning: `ask --configure` is deprecated, use `ask config` instead.");
+        return config_setup::configure().await;
+    }
 
-        let stdin_content = if !atty::is(atty::Stream::Stdin) {
-            let mut piped_input = String::new();
-            io::stdin().read_to_string(&mut piped_input)?;
-            Some(piped_input)
-        } else {
-            None
-        };
-
-        let user_question = match &args.input {
-            Some(input) => input,
-            None => {
-                eprintln!("Please enter your question.");
-                std::process::exit(1);
+    match args.command {
+        Some(cli::Command::Chat(chat)) => {
+            let chat_matches = matches.subcommand_matches("chat").unwrap_or(&matches);
+            ask(chat, chat_matches).await?;
+        }
+        Some(cli::Command::Config) => config_setup::configure().await?,
+        Some(cli::Command::Cache { action }) => match action {
+            cli::CacheAction::Stats => {
+                let (count, bytes) = services::cache::stats()?;
+                println!(
+                    "{} cached answers, {:.1} KiB in {}",
+                    count,
+                    bytes as f64 / 1024.0,
+                    services::cache::get_cache_dir().display()
+                );
+            }
+            cli::CacheAction::Clear => {
+                let removed = services::cache::clear()?;
+                println!("Removed {} cached answers.", removed);
+            }
+        },
+        Some(cli::Command::Git { options, action }) => {
+            let request = RequestOptions::from(&options);
+            match action {
+                cli::GitAction::Commit { yes } => services::git::commit(&request, yes).await?,
+                cli::GitAction::Review { range } => {
+                    services::git::review(&request, range.as_deref()).await?
+                }
             }
-        };
+        }
+        Some(cli::Command::Models { action }) => match action {
+            cli::ModelsAction::List { filter, owner } => {
+                services::api::print_models(filter.as_deref(), owner.as_deref()).await?;
+            }
+        },
+        Some(cli::Command::Completions { shell }) => {
+            if let Some(completer) = clap_complete::env::Shells::builtins().completer(&shell) {
+                completer.write_registration("COMPLETE", "ask", "ask", "ask", &mut io::stdout())?;
+            }
+        }
+        Some(cli::Command::ShellInit { shell }) => print!("{}", shell.init_script()),
+        Some(cli::Command::Usage { since, by }) => {
+            let config = config_setup::Config::load()?;
+            services::ledger::report(&config, since, by)?;
+        }
+        None => ask(args.chat, &matches).await?,
+    }
 
-        let config: Config = Config::load()?;
+    Ok(())
+}
 
-        let prompt = models::prompt::format_prompt(
-            &config.system_prompt,
-            stdin_content.as_deref(),
-            user_question,
-        );
+/// Answers a question, from `ask <QUESTION>` or `ask chat`. `matches` are the
+/// ones the options were parsed from.
+async fn ask(args: cli::ChatArgs, matches: &ArgMatches) -> Result<(), AskError> {
+    let last_command = args
+        .explain_last
+        .then(services::shell::last_command)
+        .transpose()?;
 
-        services::api::chat(prompt, args).await?;
-    }
+    let stdin = match &last_command {
+        // The recorded stderr takes the place of piped input
+        Some(last) => last.stderr.clone(),
+        None if !atty::is(atty::Stream::Stdin) => {
+            let mut piped_input = String::new();
+            io::stdin().read_to_string(&mut piped_input)?;
+            Some(piped_input)
+        }
+        None => None,
+    };
 
-    Ok(())
+    let options = args.options(matches.value_source("output") == Some(ValueSource::CommandLine));
+    let question = Question {
+        words: args.input,
+        stdin,
+        last_command,
+        editor: args.editor,
+    };
+    services::question::ask(question, options).await
 }
@@ -0,0 +1,173 @@
+/// A fenced code block taken from an answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub code: String,
+}
+
+impl CodeBlock {
+    /// File extension used when saving the block.
+    pub fn extension(&self) -> &str {
+        match self.language.as_deref().map(str::to_lowercase).as_deref() {
+            Some("bash" | "sh" | "shell" | "zsh" | "console") => "sh",
+            Some("fish") => "fish",
+            Some("powershell" | "ps1" | "pwsh") => "ps1",
+            Some("python" | "py") => "py",
+            Some("rust" | "rs") => "rs",
+            Some("javascript" | "js") => "js",
+            Some("typescript" | "ts") => "ts",
+            Some("go" | "golang") => "go",
+            Some("c") => "c",
+            Some("cpp" | "c++") => "cpp",
+            Some("java") => "java",
+            Some("ruby" | "rb") => "rb",
+            Some("json") => "json",
+            Some("yaml" | "yml") => "yaml",
+            Some("toml") => "toml",
+            Some("sql") => "sql",
+            Some("html") => "html",
+            Some("css") => "css",
+            Some("dockerfile") => "Dockerfile",
+            _ => "txt",
+        }
+    }
+}
+
+/// Which code blocks to keep.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    All,
+    /// The Nth block, starting at 1
+    Index(usize),
+    /// Blocks tagged with this language
+    Language(String),
+}
+
+impl std::str::FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("Code block index starts at 1".to_string()),
+            Ok(index) => Ok(Selector::Index(index)),
+            Err(_) if s == "all" => Ok(Selector::All),
+            Err(_) if !s.is_empty() => Ok(Selector::Language(s.to_lowercase())),
+            Err(_) => Err("Code block selector cannot be empty".to_string()),
+        }
+    }
+}
+
+impl Selector {
+    pub fn select(&self, blocks: Vec<CodeBlock>) -> Vec<CodeBlock> {
+        match self {
+            Selector::All => blocks,
+            Selector::Index(index) => blocks.into_iter().skip(index - 1).take(1).collect(),
+            Selector::Language(language) => blocks
+                .into_iter()
+                .filter(|block| {
+                    block
+                        .language
+                        .as_deref()
+                        .is_some_and(|l| l.eq_ignore_ascii_case(language))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Collects the fenced (``` or ~~~) code blocks of a markdown answer. An
+/// unclosed block at the end is kept, since answers can be cut short.
+pub fn extract_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<(&str, Option<String>, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match current.as_mut() {
+            None => {
+                let fence = ["```", "~~~"]
+                    .into_iter()
+                    .find(|fence| trimmed.starts_with(fence));
+                if let Some(fence) = fence {
+                    let info = trimmed.trim_start_matches(fence.chars().next().unwrap());
+                    let language = info.split_whitespace().next().map(str::to_string);
+                    current = Some((fence, language, Vec::new()));
+                }
+            }
+            Some((fence, _, lines)) => {
+                if trimmed.starts_with(*fence)
+                    && trimmed.trim_end().chars().all(|c| fence.starts_with(c))
+                {
+                    let (_, language, lines) = current.take().unwrap();
+                    blocks.push(CodeBlock {
+                        language,
+                        code: lines.join("\n"),
+                    });
+                } else {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+
+    if let Some((_, language, lines)) = current {
+        blocks.push(CodeBlock {
+            language,
+            code: lines.join("\n"),
+        });
+    }
+    blocks
+}
+
+/// Models sometimes wrap a whole answer in a markdown fence even when asked
+/// not to. Returns the content of that fence, or the trimmed answer.
+pub fn strip_code_fence(answer: &str) -> &str {
+    let trimmed = answer.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => {
+            let body = rest.split_once('\n').map(|(_, body)| body).unwrap_or("");
+            body.trim_end().trim_end_matches("```").trim()
+        }
+        None => trimmed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANSWER: &str = "Run this:\n```bash\necho hi\n```\nThen:\n```python\nprint(1)\nprint(2)\n```\nPlain:\n~~~\nraw\n~~~\n";
+
+    #[test]
+    fn extracts_fenced_blocks() {
+        let blocks = extract_code_blocks(ANSWER);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].language.as_deref(), Some("bash"));
+        assert_eq!(blocks[0].code, "echo hi");
+        assert_eq!(blocks[1].code, "print(1)\nprint(2)");
+        assert_eq!(blocks[2].language, None);
+        assert_eq!(blocks[2].extension(), "txt");
+    }
+
+    #[test]
+    fn selects_by_index_and_language() {
+        let by_index: Selector = "2".parse().unwrap();
+        let blocks = by_index.select(extract_code_blocks(ANSWER));
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].language.as_deref(), Some("python"));
+
+        let by_language: Selector = "Bash".parse().unwrap();
+        let blocks = by_language.select(extract_code_blocks(ANSWER));
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].code, "echo hi");
+
+        assert!("0".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn keeps_unclosed_block() {
+        let blocks = extract_code_blocks("```sh\nls -la\n");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].code, "ls -la");
+    }
+}
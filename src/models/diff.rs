@@ -0,0 +1,94 @@
+use super::tokens::estimate_tokens;
+use super::truncate::{truncate, Budget, TruncateStrategy};
+
+/// The part of a unified diff that touches one file.
+#[derive(Debug, PartialEq)]
+pub struct FileDiff<'a> {
+    pub path: &'a str,
+    pub patch: &'a str,
+}
+
+/// Splits the output of `git diff` at every `diff --git` header. Anything
+/// before the first header is dropped.
+pub fn split_files(diff: &str) -> Vec<FileDiff<'_>> {
+    let mut starts: Vec<usize> = diff
+        .match_indices("diff --git ")
+        .map(|(index, _)| index)
+        .filter(|&index| index == 0 || diff[..index].ends_with('\n'))
+        .collect();
+    starts.push(diff.len());
+
+    starts
+        .windows(2)
+        .map(|range| {
+            let patch = &diff[range[0]..range[1]];
+            let header = patch.lines().next().unwrap_or("");
+            let path = header
+                .rsplit_once(" b/")
+                .map(|(_, path)| path)
+                .unwrap_or(header);
+            FileDiff { path, patch }
+        })
+        .collect()
+}
+
+/// Joins the file diffs, and when they do not fit in `max_tokens` gives every
+/// file an equal share and keeps the start of each, so no file goes missing.
+pub fn fit_files(files: &[FileDiff], max_tokens: usize) -> String {
+    let total: usize = files.iter().map(|file| estimate_tokens(file.patch)).sum();
+    if total <= max_tokens {
+        return files.iter().map(|file| file.patch).collect();
+    }
+
+    let budget = Budget {
+        max_tokens: (max_tokens / files.len().max(1)).max(1),
+        strategy: TruncateStrategy::Head,
+    };
+    files
+        .iter()
+        .map(|file| {
+            let (patch, _) = truncate(file.patch, &budget);
+            if patch.ends_with('\n') {
+                patch
+            } else {
+                format!("{}\n", patch)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/src/main.rs b/src/main.rs\n\
+                        --- a/src/main.rs\n\
+                        +++ b/src/main.rs\n\
+                        @@ -1 +1 @@\n\
+                        -old\n\
+                        +new\n\
+                        diff --git a/README.md b/README.md\n\
+                        +docs mention diff --git inline\n";
+
+    #[test]
+    fn splits_diff_per_file() {
+        let files = split_files(DIFF);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/main.rs");
+        assert!(files[0].patch.ends_with("+new\n"));
+        assert_eq!(files[1].path, "README.md");
+        assert!(split_files("").is_empty());
+    }
+
+    #[test]
+    fn keeps_every_file_when_over_budget() {
+        let big = format!(
+            "diff --git a/a b/a\n{}diff --git a/b b/b\n+small\n",
+            "+line\n".repeat(500)
+        );
+        let fitted = fit_files(&split_files(&big), 50);
+        assert!(fitted.contains("diff --git a/a b/a"));
+        assert!(fitted.contains("diff --git a/b b/b"));
+        assert!(fitted.len() < big.len());
+    }
+}
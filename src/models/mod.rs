@@ -1 +1,6 @@
+pub mod code;
+pub mod diff;
 pub mod prompt;
+pub mod template;
+pub mod tokens;
+pub mod truncate;
@@ -1,7 +1,30 @@
+use super::template::{Expanded, Variables};
+use super::tokens::estimate_tokens;
+use super::truncate::{truncate, Budget};
+use crate::error::AskError;
+
+/// Truncates piped input to the budget, warning about what was cut.
+pub fn fit_to_budget(stdin: &str, budget: Option<&Budget>) -> String {
+    match budget {
+        Some(budget) => {
+            let (truncated, cut) = truncate(stdin, budget);
+            if let Some(cut) = cut {
+                eprintln!("{}", cut);
+            }
+            truncated
+        }
+        None => stdin.to_string(),
+    }
+}
+
+/// Prompt with the piped input under a `# STDIN` heading, or `# <stdin_label>`
+/// when given, followed by the question.
 pub fn format_prompt(
     system_prompt: &str,
     stdin_content: Option<&str>,
+    stdin_label: Option<&str>,
     user_question: &str,
+    budget: Option<&Budget>,
 ) -> String {
     let mut prompt = String::with_capacity(
         system_prompt.len()
@@ -17,8 +40,11 @@ pub fn format_prompt(
     // if stdin  is provided, add it to the prompt
     if let Some(stdin) = stdin_content {
         if !stdin.trim().is_empty() {
-            prompt.push_str("# STDIN\n");
-            prompt.push_str(stdin);
+            let stdin = fit_to_budget(stdin, budget);
+            prompt.push_str("# ");
+            prompt.push_str(stdin_label.unwrap_or("STDIN"));
+            prompt.push('\n');
+            prompt.push_str(&stdin);
             prompt.push_str("\n\n");
         }
     }
@@ -30,6 +56,167 @@ pub fn format_prompt(
     prompt
 }
 
+/// Prompt built from a template instead of the `# STDIN` / `# Question`
+/// layout. The template decides where the piped input goes. The piped input
+/// and the expanded files, commands and variables share the budget, each cut
+/// in proportion to its size when they do not fit together.
+pub fn format_template_prompt(
+    system_prompt: &str,
+    template: &str,
+    stdin_content: Option<&str>,
+    user_input: &str,
+    budget: Option<&Budget>,
+) -> Result<String, AskError> {
+    let mut expanded = Expanded::new(template)?;
+    let mut stdin = stdin_content.unwrap_or("").to_string();
+    if !expanded.uses_stdin() {
+        if !stdin.trim().is_empty() {
+            eprintln!("The template does not use {{{{stdin}}}}, the piped input is ignored.");
+        }
+        stdin.clear();
+    }
+
+    if let Some(budget) = budget {
+        let total = estimate_tokens(&stdin)
+            + expanded
+                .expansions_mut()
+                .map(|text| estimate_tokens(text))
+                .sum::<usize>();
+        if total > budget.max_tokens {
+            for text in expanded.expansions_mut().chain([&mut stdin]) {
+                let share = budget.share(estimate_tokens(text), total);
+                *text = fit_to_budget(text, Some(&share));
+            }
+        }
+    }
+
+    let rendered = expanded.fill(&Variables {
+        stdin: &stdin,
+        input: user_input,
+    });
+    Ok(format!("{}\n\n{}", system_prompt, rendered))
+}
+
+/// Prompt asking the model to condense one piece of oversized input.
+pub fn format_summary_prompt(content: &str) -> String {
+    format!(
+        "Summarize the following content. Keep errors, warnings, names, numbers and \
+         anything else that could be needed to answer questions about it.\n\n# CONTENT\n{}",
+        content
+    )
+}
+
+/// Question sent with one chunk in chunked mode.
+pub fn format_chunk_question(user_question: &str, part: usize, total: usize) -> String {
+    format!(
+        "The input above is part {} of {}. Answer the question using only this part, \
+         and say briefly if this part is not relevant.\n\n{}",
+        part, total, user_question
+    )
+}
+
+/// Joins the per-chunk answers so they can be sent as the input of the reduce step.
+pub fn format_partial_answers(partials: &[String]) -> String {
+    partials
+        .iter()
+        .enumerate()
+        .map(|(index, answer)| format!("## Part {}\n{}", index + 1, answer))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Question for the reduce step of chunked mode.
+pub fn format_reduce_question(user_question: &str) -> String {
+    format!(
+        "The input above contains partial answers, each written from one part of a larger input. \
+         Merge them into a single answer to the question below.\n\n{}",
+        user_question
+    )
+}
+
+/// Question asking for a commit message for the staged diff passed as input.
+pub fn format_commit_question(stat: &str) -> String {
+    format!(
+        "The input above is a staged git diff, changed files:\n{}\n\
+         Write a commit message for it in the Conventional Commits format: a subject line \
+         `type(scope): summary` of at most 72 characters, then a blank line and a short body \
+         saying what changed and why when the subject alone is not enough. \
+         Reply with only the commit message.",
+        stat.trim_end()
+    )
+}
+
+/// Question asking for review comments on the diff of one file.
+pub fn format_review_question(path: &str) -> String {
+    format!(
+        "The input above is the diff of {}. Review it: point out bugs, risky changes and \
+         unclear code, citing the lines concerned. Reply with a short list of comments, \
+         or `No comments.` if there is nothing worth changing.",
+        path
+    )
+}
+
+/// Question asking why a shell command failed. Its stderr, when recorded, is
+/// passed as input; `note` is what the user added on the command line.
+pub fn format_explain_question(
+    command: &str,
+    status: i32,
+    cwd: Option<&str>,
+    has_output: bool,
+    note: Option<&str>,
+) -> String {
+    let mut question = if has_output {
+        format!(
+            "The input above is the stderr of this shell command, which exited with status {}",
+            status
+        )
+    } else {
+        format!("This shell command exited with status {}", status)
+    };
+    if let Some(cwd) = cwd {
+        question.push_str(&format!(" in {}", cwd));
+    }
+    question.push_str(&format!(
+        ":\n\n    {}\n\nExplain briefly why it failed and how to fix it. \
+         If a corrected command would work, give it on its own line.",
+        command
+    ));
+    if let Some(note) = note {
+        question.push_str("\n\n");
+        question.push_str(note);
+    }
+    question
+}
+
+/// Question for command mode, where the answer replaces the command line.
+pub fn format_command_question(request: &str, shell: &str, os: &str) -> String {
+    format!(
+        "Reply with only a single {} command for {} that does the following, \
+         without explanation or markdown:\n{}",
+        shell, os, request
+    )
+}
+
+/// Instruction appended in JSON mode. Providers in `json_object` mode expect
+/// the word JSON to appear in the prompt.
+pub fn format_json_instruction(schema: Option<&serde_json::Value>) -> String {
+    match schema {
+        Some(schema) => format!(
+            "Respond with only a JSON value that matches this JSON schema, without markdown:\n{}",
+            schema
+        ),
+        None => "Respond with only a JSON value, without markdown.".to_string(),
+    }
+}
+
+/// Follow-up sent when the previous JSON answer failed validation.
+pub fn format_json_retry(errors: &[String]) -> String {
+    format!(
+        "The previous response was rejected:\n- {}\nRespond again with only the corrected JSON.",
+        errors.join("\n- ")
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,7 +227,7 @@ mod tests {
         let stdin = "Stdin content";
         let question = "User question";
 
-        let result = format_prompt(system, Some(stdin), question);
+        let result = format_prompt(system, Some(stdin), None, question, None);
         assert!(result.contains(system));
         assert!(result.contains("# STDIN"));
         assert!(result.contains(stdin));
@@ -53,10 +240,91 @@ mod tests {
         let system = "System prompt";
         let question = "User question";
 
-        let result = format_prompt(system, None, question);
+        let result = format_prompt(system, None, None, question, None);
         assert!(result.contains(system));
         assert!(!result.contains("# STDIN"));
         assert!(result.contains("# Question"));
         assert!(result.contains(question));
     }
+
+    #[test]
+    fn labels_stdin() {
+        let result = format_prompt(
+            "System prompt",
+            Some("error[E0382]"),
+            Some("Compiler output"),
+            "why?",
+            None,
+        );
+        assert!(result.contains("# Compiler output\nerror[E0382]\n\n# Question\nwhy?"));
+        assert!(!result.contains("# STDIN"));
+    }
+
+    #[test]
+    fn truncates_stdin_to_budget() {
+        use crate::models::truncate::TruncateStrategy;
+
+        let stdin = "0123456789\n".repeat(100);
+        let budget = Budget {
+            max_tokens: 10,
+            strategy: TruncateStrategy::Tail,
+        };
+
+        let result = format_prompt(
+            "System prompt",
+            Some(&stdin),
+            None,
+            "User question",
+            Some(&budget),
+        );
+        assert!(result.contains("[... truncated ...]"));
+        assert!(result.len() < stdin.len());
+        assert!(result.contains("# Question"));
+    }
+
+    #[test]
+    fn formats_template_prompt() {
+        let result = format_template_prompt(
+            "System prompt",
+            "Review:\n{{stdin}}\nNotes: {{input}}",
+            Some("diff"),
+            "be brief",
+            None,
+        )
+        .unwrap();
+        assert_eq!(result, "System prompt\n\nReview:\ndiff\nNotes: be brief");
+        assert!(!result.contains("# Question"));
+    }
+
+    #[test]
+    fn fits_template_expansions_to_budget() {
+        use crate::models::truncate::TruncateStrategy;
+
+        let budget = Budget {
+            max_tokens: 100,
+            strategy: TruncateStrategy::Head,
+        };
+        let output = "output line\n".repeat(200);
+        let result = format_template_prompt(
+            "System prompt",
+            &format!(
+                "Logs:\n{{{{shell:printf '{}'}}}}\n{{{{stdin}}}}",
+                output.replace('\n', "\\n")
+            ),
+            Some(&"stdin line\n".repeat(20)),
+            "",
+            Some(&budget),
+        )
+        .unwrap();
+        assert!(estimate_tokens(&result) < 130);
+        assert!(result.contains("output line"));
+        assert!(result.contains("stdin line"));
+    }
+
+    #[test]
+    fn labels_partial_answers_in_order() {
+        let partials = vec!["first".to_string(), "second".to_string()];
+        let result = format_partial_answers(&partials);
+        assert_eq!(result, "## Part 1\nfirst\n\n## Part 2\nsecond");
+    }
 }
@@ -0,0 +1,166 @@
+use std::env;
+use std::process::Command;
+
+use crate::error::AskError;
+
+/// Values of the `{{stdin}}` and `{{input}}` placeholders.
+pub struct Variables<'a> {
+    pub stdin: &'a str,
+    pub input: &'a str,
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    /// The trimmed text between `{{` and `}}`
+    Placeholder(&'a str),
+}
+
+fn parse(template: &str) -> Result<Vec<Part<'_>>, AskError> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        parts.push(Part::Text(&rest[..start]));
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| AskError::Usage("Unclosed {{ in template".to_string()))?;
+        parts.push(Part::Placeholder(after[..end].trim()));
+        rest = &after[end + 2..];
+    }
+    parts.push(Part::Text(rest));
+    Ok(parts)
+}
+
+enum Piece {
+    Text(String),
+    /// Output of `{{file:}}`, `{{env:}}` or `{{shell:}}`
+    Expansion(String),
+    Stdin,
+    Input,
+}
+
+/// A template with `{{file:path}}`, `{{env:VAR}}` and `{{shell:command}}`
+/// already expanded, so their size is known before `{{stdin}}` and
+/// `{{input}}` are filled in.
+pub struct Expanded {
+    pieces: Vec<Piece>,
+}
+
+impl Expanded {
+    pub fn new(template: &str) -> Result<Self, AskError> {
+        let pieces = parse(template)?
+            .into_iter()
+            .map(|part| match part {
+                Part::Text(text) => Ok(Piece::Text(text.to_string())),
+                Part::Placeholder("stdin") => Ok(Piece::Stdin),
+                Part::Placeholder("input") => Ok(Piece::Input),
+                Part::Placeholder(placeholder) => expand(placeholder).map(Piece::Expansion),
+            })
+            .collect::<Result<_, AskError>>()?;
+        Ok(Self { pieces })
+    }
+
+    /// Whether the template places the piped input itself.
+    pub fn uses_stdin(&self) -> bool {
+        self.pieces
+            .iter()
+            .any(|piece| matches!(piece, Piece::Stdin))
+    }
+
+    /// The expanded texts, to fit them to a budget.
+    pub fn expansions_mut(&mut self) -> impl Iterator<Item = &mut String> {
+        self.pieces.iter_mut().filter_map(|piece| match piece {
+            Piece::Expansion(text) => Some(text),
+            _ => None,
+        })
+    }
+
+    /// Fills in `{{stdin}}` and `{{input}}`.
+    pub fn fill(self, variables: &Variables) -> String {
+        self.pieces
+            .into_iter()
+            .map(|piece| match piece {
+                Piece::Text(text) | Piece::Expansion(text) => text,
+                Piece::Stdin => variables.stdin.to_string(),
+                Piece::Input => variables.input.to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Fills in the placeholders of `template`: `{{stdin}}`, `{{input}}`,
+/// `{{file:path}}`, `{{env:VAR}}` and `{{shell:command}}`. Substituted text
+/// is not scanned again.
+pub fn render(template: &str, variables: &Variables) -> Result<String, AskError> {
+    Ok(Expanded::new(template)?.fill(variables))
+}
+
+fn expand(placeholder: &str) -> Result<String, AskError> {
+    match placeholder.split_once(':') {
+        Some(("file", path)) => std::fs::read_to_string(path.trim())
+            .map_err(|e| AskError::Usage(format!("Template file {}: {}", path.trim(), e))),
+        Some(("env", name)) => env::var(name.trim()).map_err(|_| {
+            AskError::Usage(format!(
+                "Environment variable {} used in the template is not set",
+                name.trim()
+            ))
+        }),
+        Some(("shell", command)) => run_shell(command.trim()),
+        _ => Err(AskError::Usage(format!(
+            "Unknown template placeholder {{{{{}}}}}",
+            placeholder
+        ))),
+    }
+}
+
+/// Output of `command`, without the trailing newline.
+fn run_shell(command: &str) -> Result<String, AskError> {
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()?
+    } else {
+        Command::new("sh").args(["-c", command]).output()?
+    };
+    if !output.status.success() {
+        return Err(AskError::Usage(format!(
+            "Template command `{}` failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches(['\n', '\r'])
+        .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARIABLES: Variables = Variables {
+        stdin: "diff --git {{input}}",
+        input: "focus on errors",
+    };
+
+    #[test]
+    fn renders_placeholders() {
+        env::set_var("ASK_TEST_TEMPLATE_LANG", "Rust");
+        let rendered = render(
+            "Review this {{ env:ASK_TEST_TEMPLATE_LANG }} diff, {{input}}:\n{{stdin}}\n{{shell:echo done}}",
+            &VARIABLES,
+        )
+        .unwrap();
+        assert_eq!(
+            rendered,
+            "Review this Rust diff, focus on errors:\ndiff --git {{input}}\ndone"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_and_unclosed_placeholders() {
+        assert!(render("{{nope}}", &VARIABLES).is_err());
+        assert!(render("{{stdin", &VARIABLES).is_err());
+        assert!(render("{{shell:exit 3}}", &VARIABLES).is_err());
+        assert!(Expanded::new("a {{ stdin }} b").unwrap().uses_stdin());
+        assert!(!Expanded::new("{{input}}").unwrap().uses_stdin());
+    }
+}
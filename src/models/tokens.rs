@@ -0,0 +1,24 @@
+/// Rough token estimate for budgeting. Most tokenizers average around four
+/// characters per token for English text and code, which is close enough to
+/// decide whether content fits a context window.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Inverse of `estimate_tokens`, used to turn a token budget into a character budget.
+pub fn tokens_to_chars(tokens: usize) -> usize {
+    tokens.saturating_mul(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_roughly_four_chars_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        assert_eq!(tokens_to_chars(estimate_tokens("abcdefgh")), 8);
+    }
+}
@@ -0,0 +1,273 @@
+use super::tokens::{estimate_tokens, tokens_to_chars};
+use crate::error::AskError;
+use std::fmt;
+
+/// Tokens held back for the model's answer when budgeting the prompt.
+pub const RESERVED_OUTPUT_TOKENS: usize = 1_024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TruncateStrategy {
+    /// Keep the beginning of the content
+    Head,
+    /// Keep the end of the content
+    Tail,
+    /// Keep the beginning and the end, drop the middle
+    #[default]
+    Middle,
+    /// Summarize the content with the model before sending
+    Summarize,
+}
+
+impl std::str::FromStr for TruncateStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "head" => Ok(TruncateStrategy::Head),
+            "tail" => Ok(TruncateStrategy::Tail),
+            "middle" => Ok(TruncateStrategy::Middle),
+            "summarize" => Ok(TruncateStrategy::Summarize),
+            _ => Err(format!("Invalid truncate strategy: {}", s)),
+        }
+    }
+}
+
+/// How many tokens of piped content fit alongside the rest of the prompt.
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    pub max_tokens: usize,
+    pub strategy: TruncateStrategy,
+}
+
+impl Budget {
+    /// Budget left for content once the system prompt, the question and the
+    /// reserved output tokens are taken out of the model's context size.
+    pub fn for_prompt(
+        context_size: usize,
+        system_prompt: &str,
+        user_question: &str,
+        strategy: TruncateStrategy,
+    ) -> Self {
+        let used = estimate_tokens(system_prompt)
+            + estimate_tokens(user_question)
+            + RESERVED_OUTPUT_TOKENS;
+        Self {
+            max_tokens: context_size.saturating_sub(used),
+            strategy,
+        }
+    }
+
+    /// Fails when nothing is left for content, which would otherwise be cut
+    /// away entirely or split into one character chunks.
+    pub fn ensure_room(&self) -> Result<(), AskError> {
+        if self.max_tokens == 0 {
+            return Err(AskError::Usage(
+                "The question alone exceeds the context window, with room kept for the answer. \
+                 Shorten it or pipe the long part instead"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Part of the budget for one of several contents sharing it, in
+    /// proportion to its `tokens` out of `total`.
+    pub fn share(&self, tokens: usize, total: usize) -> Self {
+        Self {
+            max_tokens: self.max_tokens * tokens / total.max(1),
+            strategy: self.strategy,
+        }
+    }
+
+    pub fn fits(&self, content: &str) -> bool {
+        estimate_tokens(content) <= self.max_tokens
+    }
+}
+
+/// What was removed from the content, reported to the user as a warning.
+#[derive(Debug, PartialEq)]
+pub struct Cut {
+    pub strategy: TruncateStrategy,
+    pub original_tokens: usize,
+    pub budget_tokens: usize,
+    pub dropped_lines: usize,
+}
+
+impl fmt::Display for Cut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = match self.strategy {
+            TruncateStrategy::Head => "the end",
+            TruncateStrategy::Tail => "the beginning",
+            TruncateStrategy::Middle | TruncateStrategy::Summarize => "the middle",
+        };
+        write!(
+            f,
+            "Warning: input is ~{} tokens, over the budget of {} tokens. Dropped ~{} tokens ({} lines) from {}.",
+            self.original_tokens,
+            self.budget_tokens,
+            self.original_tokens - self.budget_tokens,
+            self.dropped_lines,
+            location
+        )
+    }
+}
+
+/// Marker inserted where content was removed.
+const MARKER: &str = "[... truncated ...]\n";
+
+/// Byte offset of the `max_chars`-th character, backed off to the last line
+/// break so lines are not cut in half.
+fn head_boundary(content: &str, max_chars: usize) -> usize {
+    let end = content
+        .char_indices()
+        .nth(max_chars)
+        .map(|(i, _)| i)
+        .unwrap_or(content.len());
+    match content[..end].rfind('\n') {
+        Some(i) if end < content.len() && i > 0 => i + 1,
+        _ => end,
+    }
+}
+
+/// Byte offset where the last `max_chars` characters start, moved forward to
+/// the next line break so lines are not cut in half.
+fn tail_boundary(content: &str, max_chars: usize) -> usize {
+    let total = content.chars().count();
+    if max_chars >= total {
+        return 0;
+    }
+    let start = content
+        .char_indices()
+        .nth(total - max_chars)
+        .map(|(i, _)| i)
+        .unwrap_or(content.len());
+    match content[start..].find('\n') {
+        Some(i) if start + i + 1 < content.len() => start + i + 1,
+        _ => start,
+    }
+}
+
+fn with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    }
+}
+
+/// Shrinks `content` to fit the budget. Content that already fits is returned
+/// unchanged. `Summarize` must be resolved by the caller beforehand, here it
+/// falls back to `Middle`.
+pub fn truncate(content: &str, budget: &Budget) -> (String, Option<Cut>) {
+    if budget.fits(content) {
+        return (content.to_string(), None);
+    }
+
+    let max_chars = tokens_to_chars(budget.max_tokens);
+    let (kept, truncated) = match budget.strategy {
+        TruncateStrategy::Head => {
+            let head = &content[..head_boundary(content, max_chars)];
+            (vec![head], format!("{}{}", with_newline(head), MARKER))
+        }
+        TruncateStrategy::Tail => {
+            let tail = &content[tail_boundary(content, max_chars)..];
+            (vec![tail], format!("{}{}", MARKER, tail))
+        }
+        TruncateStrategy::Middle | TruncateStrategy::Summarize => {
+            let end = head_boundary(content, max_chars / 2);
+            let start = tail_boundary(content, max_chars / 2).max(end);
+            let (head, tail) = (&content[..end], &content[start..]);
+            (
+                vec![head, tail],
+                format!("{}{}{}", with_newline(head), MARKER, tail),
+            )
+        }
+    };
+
+    let kept_lines: usize = kept.iter().map(|part| part.lines().count()).sum();
+    let cut = Cut {
+        strategy: budget.strategy,
+        original_tokens: estimate_tokens(content),
+        budget_tokens: budget.max_tokens,
+        dropped_lines: content.lines().count().saturating_sub(kept_lines),
+    };
+    (truncated, Some(cut))
+}
+
+/// Splits `content` into consecutive chunks of at most `max_tokens`, breaking
+/// on line boundaries where possible.
+pub fn split_chunks(content: &str, max_tokens: usize) -> Vec<&str> {
+    let max_chars = tokens_to_chars(max_tokens).max(1);
+    let mut chunks = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let end = head_boundary(rest, max_chars);
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbered_lines(n: usize) -> String {
+        (0..n).map(|i| format!("line {:03}\n", i)).collect()
+    }
+
+    fn budget(max_tokens: usize, strategy: TruncateStrategy) -> Budget {
+        Budget {
+            max_tokens,
+            strategy,
+        }
+    }
+
+    #[test]
+    fn leaves_fitting_content_untouched() {
+        let content = numbered_lines(3);
+        let (text, cut) = truncate(&content, &budget(1_000, TruncateStrategy::Middle));
+        assert_eq!(text, content);
+        assert!(cut.is_none());
+    }
+
+    #[test]
+    fn keeps_requested_part() {
+        let content = numbered_lines(100);
+
+        let (head, cut) = truncate(&content, &budget(25, TruncateStrategy::Head));
+        assert!(head.starts_with("line 000"));
+        assert!(!head.contains("line 099"));
+        assert!(cut.is_some());
+
+        let (tail, _) = truncate(&content, &budget(25, TruncateStrategy::Tail));
+        assert!(!tail.contains("line 000"));
+        assert!(tail.ends_with("line 099\n"));
+
+        let (middle, _) = truncate(&content, &budget(25, TruncateStrategy::Middle));
+        assert!(middle.starts_with("line 000"));
+        assert!(middle.contains("[... truncated ...]"));
+        assert!(!middle.contains("line 050"));
+        assert!(middle.ends_with("line 099\n"));
+    }
+
+    #[test]
+    fn splits_into_chunks_on_line_boundaries() {
+        let content = numbered_lines(10);
+        let chunks = split_chunks(&content, 5);
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), content);
+        assert!(chunks.iter().all(|chunk| chunk.ends_with('\n')));
+    }
+
+    #[test]
+    fn budget_subtracts_prompt_and_reserve() {
+        let budget = Budget::for_prompt(2_048, "abcd", "abcd", TruncateStrategy::Tail);
+        assert_eq!(budget.max_tokens, 2_048 - 2 - RESERVED_OUTPUT_TOKENS);
+        assert!(budget.ensure_room().is_ok());
+        assert_eq!(
+            Budget::for_prompt(10, "", "", TruncateStrategy::Tail).max_tokens,
+            0
+        );
+    }
+}
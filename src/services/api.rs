@@ -1,23 +1,51 @@
+use futures_util::stream::{self, StreamExt, TryStreamExt};
 use reqwest::Client;
+use serde_json::{json, Value};
 use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
+use super::api_error::ApiError;
+use super::cache;
+use super::client::{build_client, with_extras};
+use super::compare::{self, Answer};
+use super::ledger;
+use super::output::{OutputFormat, Printer};
+use super::question::{AskOptions, RequestOptions};
 use super::request::{ReasoningEffort, RequestBody};
-use super::schema::{APIResponse, Message, Usage};
+use super::retry::{is_retryable_error, is_retryable_status, retry_after, RetryPolicy};
+use super::schema::{APIResponse, Message, Model, Usage};
 use super::spinner;
 use super::stream::stream;
-use crate::cli::Cli;
+use super::structured::JsonMode;
 use crate::config::setup as config;
+use crate::error::AskError;
+use crate::models::code::strip_code_fence;
+use crate::models::prompt::{
+    format_chunk_question, format_json_instruction, format_json_retry, format_prompt,
+    format_summary_prompt,
+};
+use crate::models::truncate::{split_chunks, Budget};
 use crate::services::schema::NonStreamingResponse;
 
-fn check_exists(model: &str, models: &APIResponse) -> bool {
+/// Attempts at getting a valid JSON answer before giving up.
+const MAX_JSON_ATTEMPTS: usize = 3;
+
+pub fn check_exists(model: &str, models: &APIResponse) -> bool {
     models.data.iter().any(|m| m.id == model)
 }
 
-fn load_api_key() -> Result<String, Box<dyn std::error::Error>> {
-    env::var("ASK_API_KEY").map_err(|_| "ASK_API_KEY environment variable not set".into())
+pub(crate) fn load_api_key() -> Result<String, AskError> {
+    env::var("ASK_API_KEY")
+        .map_err(|_| AskError::Auth("ASK_API_KEY environment variable not set".to_string()))
+}
+
+/// DEBUG=1 or DEBUG=true prints request and response details.
+fn debug_enabled() -> bool {
+    env::var("DEBUG").is_ok_and(|debug| debug == "1" || debug == "true")
 }
 
-fn select_model(config: &config::Config, reasoning: &ReasoningEffort) -> String {
+pub fn select_model(config: &config::Config, reasoning: &ReasoningEffort) -> String {
     if *reasoning != ReasoningEffort::None {
         config.thinking_model.clone()
     } else {
@@ -25,146 +53,668 @@ fn select_model(config: &config::Config, reasoning: &ReasoningEffort) -> String
     }
 }
 
+fn join_url(base_url: &str, path: &str) -> String {
+    if base_url.ends_with("/") {
+        format!("{}{}", base_url, path)
+    } else {
+        format!("{}/{}", base_url, path)
+    }
+}
+
 fn create_endpoint(legacy_completions: &bool, base_url: &str) -> String {
     let endpoint = match legacy_completions {
         true => "completions",
         false => "chat/completions",
     };
+    join_url(base_url, endpoint)
+}
 
-    if base_url.ends_with("/") {
-        format!("{}{}", base_url, endpoint)
-    } else {
-        format!("{}/{}", base_url, endpoint)
-    }
+/// Body patches from the configuration. The temperature goes first so
+/// `extra_body` can still override it.
+pub(crate) fn config_extra(config: &config::Config) -> Vec<Value> {
+    let temperature = config
+        .temperature
+        .map(|temperature| json!({ "temperature": temperature }));
+    temperature
+        .into_iter()
+        .chain(Some(config.extra_body.clone()).filter(|patch| !patch.is_null()))
+        .collect()
 }
 
-fn build_request_body(
+/// Body patches from the configuration, then from `--extra`.
+pub(super) fn request_extra(config: &config::Config, args_extra: &[Value]) -> Vec<Value> {
+    let mut extra = config_extra(config);
+    extra.extend(args_extra.iter().cloned());
+    extra
+}
+
+pub(crate) fn build_request_body(
     model: String,
     prompt: String,
     stream: bool,
     reasoning: ReasoningEffort,
     verbose: bool,
-) -> Result<RequestBody, Box<dyn std::error::Error>> {
-    Ok(RequestBody::builder()
+    extra: &[Value],
+) -> Result<RequestBody, AskError> {
+    RequestBody::builder()
         .model(model)
-        .messages(vec![Message {
-            role: Some("user".to_string()),
-            content: Some(prompt),
-        }])
+        .messages(vec![Message::new("user", prompt)])
         .stream(stream)
         .reasoning_effort(reasoning)
         .show_reasoning(verbose)
-        .build()?)
+        .extras(extra)
+        .build()
+        .map_err(AskError::Internal)
 }
+
+/// Receives notices like retries, which the command line prints to stderr.
+pub(crate) type Notify = dyn Fn(&str) + Send + Sync;
+
+pub(crate) fn print_notice(notice: &str) {
+    eprintln!("{}", notice);
+}
+
+/// Sends a chat completions request, see [`send_with_retries`].
+pub(crate) async fn send_request(
+    client: &Client,
+    config: &config::Config,
+    api_key: &str,
+    body: RequestBody,
+    notify: &Notify,
+) -> Result<reqwest::Response, AskError> {
+    let endpoint = create_endpoint(&config.legacy_completions, &config.base_url);
+    let stream = body.is_stream();
+    let body = body.to_json();
+    send_with_retries(config, stream, notify, || {
+        client
+            .post(&endpoint)
+            .bearer_auth(api_key)
+            .header("Content-Type", "application/json")
+            .json(&body)
+    })
+    .await
+}
+
+/// Sends the request built by `request`, retrying rate limits, server errors
+/// and connection failures with backoff. Retries only happen before a
+/// successful response arrives, so a stream that has started printing is
+/// never sent again.
+async fn send_with_retries(
+    config: &config::Config,
+    stream: bool,
+    notify: &Notify,
+    request: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, AskError> {
+    let policy = RetryPolicy::from_config(config);
+
+    let mut attempt = 0;
+    loop {
+        let result = with_extras(request(), config)?.send().await;
+
+        let delay = match result {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response)
+                if attempt < policy.max_retries && is_retryable_status(response.status()) =>
+            {
+                let delay = policy.delay(attempt, retry_after(response.headers()));
+                notify(&format!(
+                    "API returned {}, retrying in {:.1}s ({}/{})",
+                    response.status(),
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    policy.max_retries
+                ));
+                delay
+            }
+            // Check response code of the API
+            Ok(response) => {
+                let error = ApiError::from_response(response).await;
+                return Err(error.with_body(debug_enabled()).into());
+            }
+            Err(e) if attempt < policy.max_retries && is_retryable_error(&e, stream) => {
+                let delay = policy.delay(attempt, None);
+                notify(&format!(
+                    "Request failed ({}), retrying in {:.1}s ({}/{})",
+                    e,
+                    delay.as_secs_f64(),
+                    attempt + 1,
+                    policy.max_retries
+                ));
+                delay
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
 async fn build_request(
     config: &config::Config,
     api_key: &str,
     body: RequestBody,
     reasoning: &ReasoningEffort,
-) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
-    let client = Client::new();
-    let endpoint = create_endpoint(&config.legacy_completions, &config.base_url);
+    show_spinner: bool,
+) -> Result<reqwest::Response, AskError> {
+    let client = build_client(&config.http)?;
     // Spinner setup
-    let spinner = if reasoning == &ReasoningEffort::None {
-        spinner::create_api_spinner()
-    } else {
-        spinner::create_reasoning_spinner()
+    let spinner = match (show_spinner, reasoning) {
+        (false, _) => None,
+        (true, ReasoningEffort::None) => Some(spinner::create_api_spinner()),
+        (true, _) => Some(spinner::create_reasoning_spinner()),
     };
-    let response = client
-        .post(&endpoint)
-        .bearer_auth(api_key)
-        .header("Content-Type", "application/json")
-        .json(&serde_json::json!(body))
-        .send()
-        .await?;
-    spinner.finish();
-    // Check response code of the API
-    if !response.status().is_success() {
-        return Err(format!("API returned an error: {:#?}", response).into());
+    let response = send_request(&client, config, api_key, body, &print_notice).await;
+    if let Some(spinner) = spinner {
+        spinner.finish();
     }
-    Ok(response)
+    response
 }
 
 async fn handle_response(
     response: reqwest::Response,
     stream_enabled: bool,
-) -> Result<Option<Usage>, Box<dyn std::error::Error>> {
+    idle_timeout: Duration,
+    printer: &mut Printer,
+) -> Result<(), AskError> {
     if stream_enabled {
-        stream(response).await
+        stream(response, printer, idle_timeout).await
     } else {
         let response_text = response.text().await?;
         let response_json: NonStreamingResponse = serde_json::from_str(&response_text)?;
-        let response_string = response_json.choices[0]
+        let choice = response_json
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| AskError::Parse("No choices in response".to_string()))?;
+        if let Some(reasoning) = &choice.message.reasoning_content {
+            printer.reasoning(reasoning)?;
+        }
+        let content = choice
             .message
             .content
-            .as_ref()
-            .expect("No content in response");
-        println!("{}", response_string);
-        Ok(Some(response_json.usage))
+            .ok_or_else(|| AskError::Parse("No content in response".to_string()))?;
+        printer.delta(&content)?;
+        if let Some(finish_reason) = choice.finish_reason {
+            printer.finish_reason(finish_reason);
+        }
+        printer.usage(response_json.usage);
+        Ok(())
     }
 }
-pub async fn check_models(
-    base_url: &str,
+
+/// Answer and metadata of a single non-streaming request.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub content: String,
+    pub finish_reason: Option<String>,
+    pub usage: Usage,
+}
+
+/// Sends a single non-streaming request and returns the answer.
+pub(crate) async fn complete(
+    client: &Client,
+    config: &config::Config,
     api_key: &str,
-    model: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Make the GET request
-    let client = Client::new();
-    let body: APIResponse = client
-        .get(format!("{}/models", base_url))
-        .bearer_auth(api_key)
-        .send()
-        .await?
-        .json()
-        .await?;
+    body: RequestBody,
+    notify: &Notify,
+) -> Result<Completion, AskError> {
+    let model = body.model().to_string();
+    let response = send_request(client, config, api_key, body, notify).await?;
+    let response_json: NonStreamingResponse = response.json().await?;
+    ledger::record_with(config, &model, &response_json.usage, notify);
+    let choice = response_json
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| AskError::Parse("No choices in response".to_string()))?;
+    Ok(Completion {
+        content: choice
+            .message
+            .content
+            .ok_or_else(|| AskError::Parse("No content in response".to_string()))?,
+        finish_reason: choice.finish_reason,
+        usage: response_json.usage,
+    })
+}
 
-    // Check if the model exists
-    let model_exists = check_exists(model, &body);
+/// Sends one request per prompt, at most `config.max_concurrency` at a time,
+/// and returns the answers in the order of the prompts.
+pub(super) async fn complete_all(
+    config: &config::Config,
+    model: String,
+    prompts: Vec<String>,
+    extra: &[Value],
+    progress_message: &str,
+) -> Result<Vec<String>, AskError> {
+    let api_key = load_api_key()?;
+    let client = build_client(&config.http)?;
+    let bodies = prompts
+        .into_iter()
+        .map(|prompt| {
+            build_request_body(
+                model.clone(),
+                prompt,
+                false,
+                ReasoningEffort::None,
+                false,
+                extra,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let progress = spinner::Progress::new(progress_message, bodies.len());
 
-    if !model_exists {
-        return Err(format!(
-            "Model {} does not exist, available models are: {:?}",
-            model, body.data
-        )
-        .into());
+    let answers = stream::iter(bodies)
+        .map(|body| {
+            let (client, api_key, progress) = (&client, &api_key, &progress);
+            async move {
+                let answer = complete(client, config, api_key, body, &print_notice).await;
+                progress.inc();
+                answer.map(|completion| completion.content)
+            }
+        })
+        .buffered(config.max_concurrency.max(1))
+        .try_collect()
+        .await;
+
+    progress.finish();
+    answers
+}
+
+/// Refuses to send more than `max_chunks` requests for one input, and says
+/// how many are sent otherwise.
+fn check_chunks(config: &config::Config, count: usize) -> Result<(), AskError> {
+    if count > config.max_chunks {
+        return Err(AskError::Usage(format!(
+            "The input splits into {} chunks, one request each, over `max_chunks` ({}). \
+             Raise it in the configuration or use another --truncate strategy",
+            count, config.max_chunks
+        )));
     }
+    eprintln!("Sending the input in {} chunks.", count);
     Ok(())
 }
 
-pub async fn chat(prompt: String, args: Cli) -> Result<(), Box<dyn std::error::Error>> {
+/// Condenses content that does not fit the budget by summarizing it chunk by
+/// chunk and joining the summaries.
+pub async fn summarize(
+    content: &str,
+    budget: &Budget,
+    request: &RequestOptions,
+) -> Result<String, AskError> {
+    budget.ensure_room()?;
+    let config: config::Config = config::Config::load_with_role(request.role.as_deref())?;
+    let chunks = split_chunks(content, budget.max_tokens);
+    check_chunks(&config, chunks.len())?;
+    let prompts = chunks.into_iter().map(format_summary_prompt).collect();
+
+    let extra = request_extra(&config, &request.extra);
+    let summaries = complete_all(
+        &config,
+        config.model.clone(),
+        prompts,
+        &extra,
+        "Summarizing",
+    )
+    .await?;
+    Ok(summaries.join("\n\n"))
+}
+
+/// Map step of chunked mode: asks `question` about every chunk of `content`
+/// on its own and returns the partial answers in chunk order.
+pub async fn map_chunks(
+    content: &str,
+    question: &str,
+    budget: &Budget,
+    stdin_label: Option<&str>,
+    request: &RequestOptions,
+) -> Result<Vec<String>, AskError> {
+    budget.ensure_room()?;
+    let config: config::Config = config::Config::load_with_role(request.role.as_deref())?;
+    let chunks = split_chunks(content, budget.max_tokens);
+    let total = chunks.len();
+    check_chunks(&config, total)?;
+    let prompts = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            format_prompt(
+                &config.system_prompt,
+                Some(chunk),
+                stdin_label,
+                &format_chunk_question(question, index + 1, total),
+                None,
+            )
+        })
+        .collect();
+
+    complete_all(
+        &config,
+        select_model(&config, &request.reasoning),
+        prompts,
+        &request_extra(&config, &request.extra),
+        "Processing chunks",
+    )
+    .await
+}
+
+/// Fetches the models offered by the provider, sorted by id.
+pub(crate) async fn list_models(
+    client: &Client,
+    config: &config::Config,
+    api_key: &str,
+    notify: &Notify,
+) -> Result<APIResponse, AskError> {
+    let url = join_url(&config.base_url, "models");
+    let response = send_with_retries(config, false, notify, || {
+        client.get(&url).bearer_auth(api_key)
+    })
+    .await?;
+    APIResponse::parse(&response.text().await?)
+}
+
+/// Lists the models for the command line, remembering their ids for shell
+/// completion.
+pub(crate) async fn fetch_models(
+    config: &config::Config,
+    api_key: &str,
+) -> Result<APIResponse, AskError> {
+    let client = build_client(&config.http)?;
+    let models = list_models(&client, config, api_key, &print_notice).await?;
+    // Only used for shell completion, a failed write is not worth reporting
+    let _ = cache::put_models(&models.data);
+    Ok(models)
+}
+
+/// Prints the models whose id contains `filter` and, if given, whose owner
+/// is `owner`, ignoring case.
+pub async fn print_models(filter: Option<&str>, owner: Option<&str>) -> Result<(), AskError> {
     let config: config::Config = config::Config::load()?;
     let api_key = load_api_key()?;
-    let model = select_model(&config, &args.reasoning);
-    let body = build_request_body(model, prompt, config.stream, args.reasoning, args.verbose)?;
+    let filter = filter.map(str::to_lowercase);
+    let models: Vec<Model> = fetch_models(&config, &api_key)
+        .await?
+        .data
+        .into_iter()
+        .filter(|model| {
+            filter
+                .as_deref()
+                .is_none_or(|filter| model.id.to_lowercase().contains(filter))
+        })
+        .filter(|model| {
+            owner.is_none_or(|owner| {
+                model
+                    .owned_by
+                    .as_deref()
+                    .is_some_and(|owned_by| owned_by.eq_ignore_ascii_case(owner))
+            })
+        })
+        .collect();
+
+    let width = models.iter().map(|model| model.id.len()).max().unwrap_or(0);
+    for model in &models {
+        let context = model
+            .context_length
+            .map(|tokens| tokens.to_string())
+            .unwrap_or_default();
+        let line = format!(
+            "{:<width$}  {:<20}  {:>9}",
+            model.id,
+            model.owned_by.as_deref().unwrap_or(""),
+            context,
+            width = width
+        );
+        println!("{}", line.trim_end());
+    }
+    if models.is_empty() {
+        eprintln!("No matching models.");
+    }
+    Ok(())
+}
+
+pub async fn chat(prompt: String, options: &AskOptions) -> Result<(), AskError> {
+    let request = &options.request;
+    let config: config::Config = config::Config::load_with_role(request.role.as_deref())?;
+    let api_key = load_api_key()?;
+    let model = select_model(&config, &request.reasoning);
+    let mut printer = Printer::new(options.output(), options.verbose, model.clone())
+        .with_code(options.code.clone());
+    let body = build_request_body(
+        model,
+        prompt,
+        config.stream,
+        request.reasoning,
+        options.verbose,
+        &request_extra(&config, &request.extra),
+    )?;
 
     // dbg the body as a json string if the DEBUG environment variable is set
-    if let Ok(debug) = env::var("DEBUG") {
-        if debug == "1" || debug == "true" {
-            println!("Request Body: {:#?}", body);
+    if debug_enabled() {
+        println!(
+            "Request Body: {}",
+            serde_json::to_string_pretty(&body.to_json())?
+        );
+    }
+
+    let result = respond(&config, &api_key, body, options, &mut printer).await;
+    if let Err(e) = &result {
+        printer.error(&e.to_string());
+    }
+    ledger::check_budget(&config);
+    result
+}
+
+async fn respond(
+    config: &config::Config,
+    api_key: &str,
+    body: RequestBody,
+    options: &AskOptions,
+    printer: &mut Printer,
+) -> Result<(), AskError> {
+    let endpoint = create_endpoint(&config.legacy_completions, &config.base_url);
+    let use_cache = config.cache && !options.no_cache;
+    // The key covers what is sent besides the body: the query and headers
+    let target = with_extras(Client::new().post(&endpoint), config)?.build()?;
+    let cache_key = cache::key(target.url().as_str(), target.headers(), &body.to_json());
+
+    if use_cache && !options.refresh {
+        let ttl = Duration::from_secs(config.cache_ttl_secs);
+        if let Some(entry) = cache::get(&cache_key, ttl) {
+            cache::replay(entry, printer, config.stream).await?;
+            return printer.finish();
         }
     }
 
-    let response = build_request(&config, &api_key, body, &args.reasoning).await?;
+    let show_spinner = printer.format() == OutputFormat::Text;
+    let response = build_request(
+        config,
+        api_key,
+        body,
+        &options.request.reasoning,
+        show_spinner,
+    )
+    .await?;
 
     // If the DEBUG environment variable is set, print the response
-    if let Ok(debug) = env::var("DEBUG") {
-        if debug == "1" || debug == "true" {
-            println!("{:?}", response);
+    if debug_enabled() {
+        println!("{:?}", response);
+    }
+    let idle_timeout = Duration::from_secs(config.http.stream_idle_timeout_secs);
+    handle_response(response, config.stream, idle_timeout, printer).await?;
+    if let Some(usage) = printer.usage_info() {
+        ledger::record(config, printer.model(), usage);
+    }
+    printer.finish()?;
+
+    if use_cache {
+        // A failed write only costs a cache miss next time
+        if let Err(e) = cache::put(&cache_key, &endpoint, printer) {
+            eprintln!("Could not write the response cache: {}", e);
         }
     }
-    let usage = handle_response(response, config.stream).await?;
-    if args.verbose {
-        if let Some(usage_info) = usage {
-            println!(
-                "\n\nUsage: prompt_tokens: {}, completion_tokens: {}, total_tokens: {}",
-                usage_info.prompt_tokens, usage_info.completion_tokens, usage_info.total_tokens
+    Ok(())
+}
+
+/// Command mode: a quiet request with no spinner or streaming that prints
+/// only the suggested command, for the shell widget to put on the command line.
+pub async fn command(prompt: String, request: &RequestOptions) -> Result<(), AskError> {
+    let config: config::Config = config::Config::load_with_role(request.role.as_deref())?;
+    let api_key = load_api_key()?;
+    let client = build_client(&config.http)?;
+    let model = select_model(&config, &request.reasoning);
+    let extra = request_extra(&config, &request.extra);
+    let body = build_request_body(model, prompt, false, ReasoningEffort::None, false, &extra)?;
+    let completion = complete(&client, &config, &api_key, body, &print_notice).await?;
+    ledger::check_budget(&config);
+    println!("{}", strip_code_fence(&completion.content));
+    Ok(())
+}
+
+/// Asks every model, or `profile:model` entry, the same prompt concurrently
+/// and prints the answers side by side. Fails only when every model failed.
+pub async fn compare(
+    prompt: String,
+    models: &[String],
+    request: &RequestOptions,
+    output: OutputFormat,
+) -> Result<(), AskError> {
+    let config: config::Config = config::Config::load_with_role(request.role.as_deref())?;
+    let known: Vec<String> = [config.model.clone(), config.thinking_model.clone()]
+        .into_iter()
+        .chain(config.context_sizes.keys().cloned())
+        .chain(config.roles.values().filter_map(|role| role.model.clone()))
+        .chain(cache::cached_models())
+        .collect();
+    compare::check_models(models, &config.profiles, &known)?;
+    // Every entry with its provider, so a missing API key fails before sending
+    let targets = models
+        .iter()
+        .map(|entry| match config.profile(entry) {
+            Some((profile, model)) => Ok((
+                entry.clone(),
+                config.with_profile(profile),
+                profile.api_key()?,
+                model.to_string(),
+            )),
+            None => Ok((
+                entry.clone(),
+                config.clone(),
+                load_api_key()?,
+                entry.clone(),
+            )),
+        })
+        .collect::<Result<Vec<_>, AskError>>()?;
+    let client = build_client(&config.http)?;
+    let progress = spinner::Progress::new("Comparing", targets.len());
+
+    let answers: Vec<Answer> = stream::iter(targets)
+        .map(|(label, config, api_key, model)| {
+            let extra = request_extra(&config, &request.extra);
+            let body = build_request_body(
+                model,
+                prompt.clone(),
+                false,
+                request.reasoning,
+                false,
+                &extra,
             );
-        } else {
-            println!("No usage information available.");
+            let (client, progress) = (&client, &progress);
+            async move {
+                let started = Instant::now();
+                let result = match body {
+                    Ok(body) => complete(client, &config, &api_key, body, &print_notice).await,
+                    Err(e) => Err(e),
+                };
+                progress.inc();
+                Answer {
+                    model: label,
+                    latency: started.elapsed(),
+                    result,
+                }
+            }
+        })
+        .buffered(config.max_concurrency.max(1))
+        .collect()
+        .await;
+    progress.finish();
+
+    compare::print(&answers, output)?;
+    ledger::check_budget(&config);
+
+    if answers.iter().any(|answer| answer.result.is_ok()) {
+        return Ok(());
+    }
+    match answers.into_iter().next().map(|answer| answer.result) {
+        Some(Err(e)) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// JSON mode: requests a JSON answer, validates it against the schema and
+/// retries with the validation errors until it passes. The answer is printed
+/// as compact JSON, with no spinner or streaming.
+pub async fn chat_json(
+    prompt: String,
+    schema: Option<&Path>,
+    request: &RequestOptions,
+) -> Result<(), AskError> {
+    let config: config::Config = config::Config::load_with_role(request.role.as_deref())?;
+    let api_key = load_api_key()?;
+    let model = select_model(&config, &request.reasoning);
+    let json_mode = JsonMode::new(schema)?;
+    let client = build_client(&config.http)?;
+
+    let mut messages = vec![Message::new(
+        "user",
+        format!(
+            "{}\n\n{}",
+            prompt,
+            format_json_instruction(json_mode.schema())
+        ),
+    )];
+
+    let mut errors = Vec::new();
+    for _ in 0..MAX_JSON_ATTEMPTS {
+        let body = RequestBody::builder()
+            .model(model.clone())
+            .messages(messages.clone())
+            .stream(false)
+            .reasoning_effort(request.reasoning)
+            .response_format(json_mode.response_format())
+            .extras(&request_extra(&config, &request.extra))
+            .build()
+            .map_err(AskError::Internal)?;
+        let response = send_request(&client, &config, &api_key, body, &print_notice).await?;
+        let response_json: NonStreamingResponse = response.json().await?;
+        ledger::record(&config, &model, &response_json.usage);
+        let answer = response_json
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| AskError::Parse("No choices in response".to_string()))?
+            .message
+            .content
+            .ok_or_else(|| AskError::Parse("No content in response".to_string()))?;
+
+        match json_mode.validate(&answer) {
+            Ok(value) => {
+                println!("{}", serde_json::to_string(&value)?);
+                ledger::check_budget(&config);
+                return Ok(());
+            }
+            Err(validation_errors) => {
+                messages.push(Message::new("assistant", answer));
+                messages.push(Message::new("user", format_json_retry(&validation_errors)));
+                errors = validation_errors;
+            }
         }
     }
 
-    Ok(())
+    Err(AskError::Parse(format!(
+        "No valid JSON after {} attempts:\n{}",
+        MAX_JSON_ATTEMPTS,
+        errors.join("\n")
+    )))
 }
 
 #[cfg(test)]
@@ -176,10 +726,26 @@ mod tests {
     #[tokio::test]
     #[ignore = "requires real API key and network"]
     async fn test_check_models() {
-        let base_url = "https://api.openai.com/v1/";
+        let config = config::Config {
+            base_url: "https://api.openai.com/v1".to_string(),
+            ..Default::default()
+        };
         let api_key = env::var("ASK_API_KEY").expect("ASK_API_KEY not set");
         let model = "gpt-3.5-turbo";
-        assert!(check_models(base_url, &api_key, model).await.is_ok());
+        let models = fetch_models(&config, &api_key).await.unwrap();
+        assert!(check_exists(model, &models));
+    }
+
+    #[test]
+    fn joins_base_url_without_double_slash() {
+        assert_eq!(
+            join_url("http://host/v1/", "models"),
+            "http://host/v1/models"
+        );
+        assert_eq!(
+            join_url("http://host/v1", "models"),
+            "http://host/v1/models"
+        );
     }
 
     #[tokio::test]
@@ -188,12 +754,15 @@ mod tests {
             data: vec![
                 Model {
                     id: "model-id-0".to_string(),
+                    ..Default::default()
                 },
                 Model {
                     id: "model-id-1".to_string(),
+                    ..Default::default()
                 },
                 Model {
                     id: "model-id-2".to_string(),
+                    ..Default::default()
                 },
             ],
         };
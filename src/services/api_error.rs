@@ -0,0 +1,170 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::fmt;
+
+/// A non-2xx response from the provider, with the error message pulled out of
+/// the body. OpenAI and compatible servers send `{"error": {"message", "type",
+/// "code"}}`, Anthropic nests the same under `{"type": "error"}` and Gemini
+/// uses `{"error": {"code", "message", "status"}}`, sometimes wrapped in a list.
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: Option<String>,
+    pub kind: Option<String>,
+    pub code: Option<String>,
+    pub body: String,
+    pub show_body: bool,
+}
+
+fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+impl ApiError {
+    pub fn parse(status: StatusCode, body: String) -> Self {
+        let parsed: Option<Value> = serde_json::from_str(&body).ok();
+        let error = parsed.as_ref().map(|value| match value {
+            Value::Array(items) => items.first().unwrap_or(value),
+            _ => value,
+        });
+        let error = error.map(|value| value.get("error").unwrap_or(value));
+
+        let field = |key: &str| error.and_then(|e| e.get(key)).and_then(as_text);
+        let message = field("message").or_else(|| field("detail"));
+        let kind = field("type").or_else(|| field("status"));
+        let code = field("code").filter(|code| *code != status.as_u16().to_string());
+
+        Self {
+            status,
+            message,
+            kind,
+            code,
+            body,
+            show_body: false,
+        }
+    }
+
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Self::parse(status, body)
+    }
+
+    /// Includes the raw response body in the message, for debugging.
+    pub fn with_body(mut self, show_body: bool) -> Self {
+        self.show_body = show_body;
+        self
+    }
+
+    /// Suggests a fix for the common failure causes.
+    pub fn hint(&self) -> Option<&'static str> {
+        let text = format!(
+            "{} {} {}",
+            self.message.as_deref().unwrap_or_default(),
+            self.kind.as_deref().unwrap_or_default(),
+            self.code.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+
+        if text.contains("context")
+            || text.contains("too long")
+            || text.contains("too many tokens")
+            || text.contains("maximum")
+            || self.status == StatusCode::PAYLOAD_TOO_LARGE
+        {
+            Some("The prompt is too long for the model. Pipe less input, or use --truncate or --chunked.")
+        } else if matches!(
+            self.status,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) || text.contains("api key")
+            || text.contains("api_key")
+            || text.contains("authentication")
+        {
+            Some("Check that ASK_API_KEY holds a valid key for this provider.")
+        } else if self.status == StatusCode::TOO_MANY_REQUESTS
+            || text.contains("quota")
+            || text.contains("rate limit")
+            || text.contains("resource_exhausted")
+        {
+            Some("Rate limit or quota reached. Wait a moment, or check the plan and billing of the provider.")
+        } else if self.status == StatusCode::NOT_FOUND
+            || (text.contains("model")
+                && (text.contains("not found") || text.contains("does not exist")))
+        {
+            Some("Check the model name and base_url with `ask config`.")
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API returned {}", self.status)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message.trim())?;
+        }
+        let details: Vec<&str> = [self.kind.as_deref(), self.code.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect();
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        if let Some(hint) = self.hint() {
+            write!(f, "\nHint: {}", hint)?;
+        }
+        if self.show_body || self.message.is_none() {
+            write!(f, "\nResponse body: {}", self.body.trim())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_openai_error() {
+        let body = r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "code": "invalid_api_key"}}"#;
+        let error = ApiError::parse(StatusCode::UNAUTHORIZED, body.to_string());
+        assert_eq!(error.message.as_deref(), Some("Incorrect API key provided"));
+        assert_eq!(error.code.as_deref(), Some("invalid_api_key"));
+        assert!(error.hint().unwrap().contains("ASK_API_KEY"));
+        assert_eq!(
+            error.to_string().lines().next().unwrap(),
+            "API returned 401 Unauthorized: Incorrect API key provided (invalid_request_error, invalid_api_key)"
+        );
+    }
+
+    #[test]
+    fn parses_gemini_error_list() {
+        let body = r#"[{"error": {"code": 429, "message": "Resource has been exhausted (e.g. check quota).", "status": "RESOURCE_EXHAUSTED"}}]"#;
+        let error = ApiError::parse(StatusCode::TOO_MANY_REQUESTS, body.to_string());
+        assert_eq!(error.kind.as_deref(), Some("RESOURCE_EXHAUSTED"));
+        assert_eq!(error.code, None);
+        assert!(error.hint().unwrap().contains("quota"));
+    }
+
+    #[test]
+    fn parses_anthropic_error() {
+        let body = r#"{"type": "error", "error": {"type": "invalid_request_error", "message": "prompt is too long: 210000 tokens > 200000 maximum"}}"#;
+        let error = ApiError::parse(StatusCode::BAD_REQUEST, body.to_string());
+        assert_eq!(error.kind.as_deref(), Some("invalid_request_error"));
+        assert!(error.hint().unwrap().contains("--truncate"));
+    }
+
+    #[test]
+    fn falls_back_to_body() {
+        let error = ApiError::parse(StatusCode::BAD_GATEWAY, "upstream down".to_string());
+        assert!(error.message.is_none());
+        assert!(error.to_string().contains("Response body: upstream down"));
+    }
+}
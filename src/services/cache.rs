@@ -0,0 +1,215 @@
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::output::Printer;
+use super::schema::{Model, Usage};
+use crate::error::AskError;
+
+/// Pause between replayed chunks when simulating a stream.
+const REPLAY_DELAY: Duration = Duration::from_millis(8);
+
+/// A stored answer, replayed through the same `Printer` as a live one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub created_at: u64,
+    pub endpoint: String,
+    pub content: String,
+    #[serde(default)]
+    pub reasoning: String,
+    pub finish_reason: Option<String>,
+    pub usage: Option<Usage>,
+}
+
+pub fn get_cache_dir() -> PathBuf {
+    env::var("ASK_CACHE_DIR")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("XDG_CACHE_HOME").map(|dir| PathBuf::from(dir).join("ask")))
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".cache").join("ask")))
+        .unwrap_or_else(|_| PathBuf::from("./.ask-cache"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Cache key: SHA-256 of the request URL with its query, the configured
+/// headers and the exact JSON body, so providers, tenants or API versions
+/// never share answers.
+pub fn key(url: &str, headers: &HeaderMap, body: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(url.as_bytes());
+    hasher.update(b"\n");
+    let mut headers: Vec<(&str, &[u8])> = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_bytes()))
+        .collect();
+    headers.sort();
+    for (name, value) in headers {
+        hasher.update(name.as_bytes());
+        hasher.update(b": ");
+        hasher.update(value);
+        hasher.update(b"\n");
+    }
+    hasher.update(body.to_string().as_bytes());
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn entry_path(key: &str) -> PathBuf {
+    get_cache_dir().join(format!("{}.json", key))
+}
+
+/// Returns the stored answer for `key` unless it is older than `ttl`.
+/// Unreadable entries are treated as missing.
+pub fn get(key: &str, ttl: Duration) -> Option<Entry> {
+    let entry_str = std::fs::read_to_string(entry_path(key)).ok()?;
+    let entry: Entry = serde_json::from_str(&entry_str).ok()?;
+    (now().saturating_sub(entry.created_at) <= ttl.as_secs()).then_some(entry)
+}
+
+pub fn put(key: &str, endpoint: &str, printer: &Printer) -> Result<(), AskError> {
+    let entry = Entry {
+        created_at: now(),
+        endpoint: endpoint.to_string(),
+        content: printer.content().to_string(),
+        reasoning: printer.reasoning_text().to_string(),
+        finish_reason: printer.finish_reason_text().map(str::to_string),
+        usage: printer.usage_info().cloned(),
+    };
+    std::fs::create_dir_all(get_cache_dir())?;
+    // Entries hold prompts and piped input, so only the user may read them
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(entry_path(key))?
+        .write_all(serde_json::to_string(&entry)?.as_bytes())?;
+    Ok(())
+}
+
+/// Feeds a stored answer to the printer. With `stream` the content is sent
+/// in small pieces so it looks the same as a live streamed answer.
+pub async fn replay(entry: Entry, printer: &mut Printer, stream: bool) -> Result<(), AskError> {
+    if !entry.reasoning.is_empty() {
+        printer.reasoning(&entry.reasoning)?;
+    }
+    if stream {
+        for piece in entry.content.split_inclusive(' ') {
+            printer.delta(piece)?;
+            tokio::time::sleep(REPLAY_DELAY).await;
+        }
+    } else {
+        printer.delta(&entry.content)?;
+    }
+    if let Some(finish_reason) = entry.finish_reason {
+        printer.finish_reason(finish_reason);
+    }
+    if let Some(usage) = entry.usage {
+        printer.usage(usage);
+    }
+    Ok(())
+}
+
+fn models_path() -> PathBuf {
+    get_cache_dir().join("models.txt")
+}
+
+/// Remembers the model ids of the provider for shell completion.
+pub fn put_models(models: &[Model]) -> Result<(), AskError> {
+    let ids: Vec<&str> = models.iter().map(|model| model.id.as_str()).collect();
+    std::fs::create_dir_all(get_cache_dir())?;
+    std::fs::write(models_path(), ids.join("\n"))?;
+    Ok(())
+}
+
+/// Model ids stored by the last `ask models list`, empty if there are none.
+pub fn cached_models() -> Vec<String> {
+    std::fs::read_to_string(models_path())
+        .map(|ids| ids.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Number of entries and their total size in bytes.
+pub fn stats() -> Result<(usize, u64), AskError> {
+    let dir = get_cache_dir();
+    if !dir.exists() {
+        return Ok((0, 0));
+    }
+    let mut count = 0;
+    let mut bytes = 0;
+    for file in std::fs::read_dir(dir)? {
+        let file = file?;
+        if file.path().extension().is_some_and(|ext| ext == "json") {
+            count += 1;
+            bytes += file.metadata()?.len();
+        }
+    }
+    Ok((count, bytes))
+}
+
+/// Deletes every entry and returns how many were removed.
+pub fn clear() -> Result<usize, AskError> {
+    let dir = get_cache_dir();
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let mut removed = 0;
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            std::fs::remove_file(path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+
+    #[test]
+    fn key_depends_on_url_headers_and_body() {
+        let body = json!({"model": "m", "messages": []});
+        let none = HeaderMap::new();
+        let a = key("http://a/chat/completions?tenant=a", &none, &body);
+        assert_eq!(a.len(), 64);
+        assert_eq!(a, key("http://a/chat/completions?tenant=a", &none, &body));
+        assert_ne!(a, key("http://a/chat/completions?tenant=b", &none, &body));
+        assert_ne!(a, key("http://b/chat/completions?tenant=a", &none, &body));
+        assert_ne!(
+            a,
+            key(
+                "http://a/chat/completions?tenant=a",
+                &none,
+                &json!({"model": "n"})
+            )
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tenant", HeaderValue::from_static("a"));
+        headers.insert("api-version", HeaderValue::from_static("1"));
+        let mut reordered = HeaderMap::new();
+        reordered.insert("api-version", HeaderValue::from_static("1"));
+        reordered.insert("x-tenant", HeaderValue::from_static("a"));
+        let b = key("http://a/chat/completions", &headers, &body);
+        assert_eq!(b, key("http://a/chat/completions", &reordered, &body));
+        assert_ne!(b, key("http://a/chat/completions", &none, &body));
+    }
+}
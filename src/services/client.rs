@@ -0,0 +1,131 @@
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Certificate, Client, Identity, NoProxy, Proxy, RequestBuilder};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::http::HttpConfig;
+use crate::config::interpolate::interpolate_env;
+use crate::config::setup::Config;
+use crate::error::AskError;
+
+fn read_file(path: &Path, what: &str) -> Result<Vec<u8>, AskError> {
+    std::fs::read(path)
+        .map_err(|e| AskError::Config(format!("Failed to read {} {}: {}", what, path.display(), e)))
+}
+
+/// Builds the HTTP client shared by all requests from the `http` section of
+/// the configuration.
+pub fn build_client(http: &HttpConfig) -> Result<Client, AskError> {
+    let mut builder =
+        Client::builder().connect_timeout(Duration::from_secs(http.connect_timeout_secs));
+
+    if let Some(timeout) = http.read_timeout_secs {
+        builder = builder.read_timeout(Duration::from_secs(timeout));
+    }
+
+    if let Some(timeout) = http.timeout_secs {
+        builder = builder.timeout(Duration::from_secs(timeout));
+    }
+
+    if let Some(proxy_url) = &http.proxy {
+        let proxy = Proxy::all(proxy_url)
+            .map_err(|e| AskError::Config(format!("Invalid proxy {}: {}", proxy_url, e)))?
+            .no_proxy(http.no_proxy.as_deref().and_then(NoProxy::from_string));
+        builder = builder.proxy(proxy);
+    }
+
+    if let Some(path) = &http.ca_bundle {
+        let certificates =
+            Certificate::from_pem_bundle(&read_file(path, "CA bundle")?).map_err(|e| {
+                AskError::Config(format!("Invalid CA bundle {}: {}", path.display(), e))
+            })?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    match (&http.client_cert, &http.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity = Identity::from_pkcs8_pem(
+                &read_file(cert, "client certificate")?,
+                &read_file(key, "client key")?,
+            )
+            .map_err(|e| AskError::Config(format!("Invalid client certificate: {}", e)))?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(AskError::Config(
+                "client_cert and client_key must be set together".to_string(),
+            ))
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| AskError::Config(format!("Failed to build the HTTP client: {}", e)))
+}
+
+/// Adds the configured extra headers and query parameters to a request, for
+/// gateways that need tenant IDs, referers or API versions.
+pub fn with_extras(
+    mut request: RequestBuilder,
+    config: &Config,
+) -> Result<RequestBuilder, AskError> {
+    for (name, value) in &config.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| AskError::Config(format!("Invalid header name {}: {}", name, e)))?;
+        let value = HeaderValue::from_str(&interpolate_env(value)?)
+            .map_err(|e| AskError::Config(format!("Invalid value for header {}: {}", name, e)))?;
+        request = request.header(name, value);
+    }
+
+    if !config.query.is_empty() {
+        let mut query = config
+            .query
+            .iter()
+            .map(|(name, value)| Ok((name.as_str(), interpolate_env(value)?)))
+            .collect::<Result<Vec<_>, AskError>>()?;
+        // A stable order keeps the URL, and so the cache key, the same
+        query.sort();
+        request = request.query(&query);
+    }
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_default_client() {
+        assert!(build_client(&HttpConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn adds_headers_and_query() {
+        let mut config = Config::default();
+        config
+            .headers
+            .insert("X-Title".to_string(), "ask".to_string());
+        config
+            .query
+            .insert("api-version".to_string(), "2024-10-21".to_string());
+
+        let request = with_extras(Client::new().get("http://localhost/models"), &config)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["x-title"], "ask");
+        assert_eq!(request.url().query(), Some("api-version=2024-10-21"));
+    }
+
+    #[test]
+    fn rejects_incomplete_client_identity() {
+        let http = HttpConfig {
+            client_cert: Some("cert.pem".into()),
+            ..HttpConfig::default()
+        };
+        assert!(matches!(build_client(&http), Err(AskError::Config(_))));
+    }
+}
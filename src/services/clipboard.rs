@@ -0,0 +1,48 @@
+use crate::error::AskError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+/// OSC 52 escape sequence asking the terminal to put `text` on the clipboard.
+/// Inside tmux the sequence is wrapped in a passthrough so it reaches the
+/// outer terminal.
+fn osc52_sequence(text: &str, in_tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if in_tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// Copies `text` through the terminal, which works over SSH without any
+/// clipboard tool on the remote host. The sequence goes to the controlling
+/// terminal so it does not end up in redirected output.
+pub fn copy(text: &str) -> Result<(), AskError> {
+    let sequence = osc52_sequence(text, env::var("TMUX").is_ok());
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes())?,
+        Err(_) => {
+            let mut stderr = std::io::stderr();
+            stderr.write_all(sequence.as_bytes())?;
+            stderr.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_osc52_sequence() {
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(
+            osc52_sequence("hi", true),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::api::Completion;
+use super::output::OutputFormat;
+use super::schema::Usage;
+use crate::config::setup::Profile;
+use crate::error::AskError;
+
+/// Checks the entries of `--compare`: `profile:model` for a model of a
+/// provider in `profiles`, or a model id of `base_url`. An unknown `name:`
+/// prefix is rejected rather than sent as a model id, unless the whole entry
+/// is a `known` model id like the tag `llama3:8b`.
+pub fn check_models(
+    models: &[String],
+    profiles: &HashMap<String, Profile>,
+    known: &[String],
+) -> Result<(), AskError> {
+    for model in models {
+        let Some((prefix, _)) = model.split_once(':') else {
+            continue;
+        };
+        if !profiles.contains_key(prefix) && !known.contains(model) {
+            return Err(AskError::Usage(format!(
+                "`{}` is not in `profiles` of the configuration. Model ids of base_url \
+                 with a colon must be configured or listed by `ask models list`",
+                prefix
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Narrowest column worth printing side by side, below that answers are
+/// printed one after the other.
+const MIN_COLUMN_WIDTH: usize = 40;
+const COLUMN_SEPARATOR: &str = " │ ";
+
+/// The answer of one compared model.
+pub struct Answer {
+    pub model: String,
+    pub latency: Duration,
+    pub result: Result<Completion, AskError>,
+}
+
+impl Answer {
+    /// `model · 1.2s · 30 prompt + 120 completion tokens`
+    fn header(&self) -> String {
+        let outcome = match &self.result {
+            Ok(completion) => format!(
+                "{} prompt + {} completion tokens",
+                completion.usage.prompt_tokens, completion.usage.completion_tokens
+            ),
+            Err(_) => "failed".to_string(),
+        };
+        format!(
+            "{} · {:.1}s · {}",
+            self.model,
+            self.latency.as_secs_f64(),
+            outcome
+        )
+    }
+
+    fn body(&self) -> String {
+        match &self.result {
+            Ok(completion) => completion.content.trim().to_string(),
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+}
+
+/// One answer in `--output json` and `--output ndjson`.
+#[derive(Serialize)]
+struct Report<'a> {
+    model: &'a str,
+    content: Option<&'a str>,
+    finish_reason: Option<&'a str>,
+    usage: Option<&'a Usage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    latency_ms: u128,
+}
+
+impl<'a> From<&'a Answer> for Report<'a> {
+    fn from(answer: &'a Answer) -> Self {
+        let completion = answer.result.as_ref().ok();
+        Self {
+            model: &answer.model,
+            content: completion.map(|c| c.content.as_str()),
+            finish_reason: completion.and_then(|c| c.finish_reason.as_deref()),
+            usage: completion.map(|c| &c.usage),
+            error: answer.result.as_ref().err().map(ToString::to_string),
+            latency_ms: answer.latency.as_millis(),
+        }
+    }
+}
+
+pub fn print(answers: &[Answer], format: OutputFormat) -> Result<(), AskError> {
+    match format {
+        OutputFormat::Text => {
+            let width =
+                atty::is(atty::Stream::Stdout).then(|| termimad::terminal_size().0 as usize);
+            match width.and_then(|width| column_width(width, answers.len())) {
+                Some(column_width) => print!("{}", columns(answers, column_width)),
+                None => print!("{}", sections(answers)),
+            }
+        }
+        OutputFormat::Json => {
+            let reports: Vec<Report> = answers.iter().map(Report::from).collect();
+            println!("{}", serde_json::to_string(&reports)?);
+        }
+        OutputFormat::Ndjson => {
+            for answer in answers {
+                println!("{}", serde_json::to_string(&Report::from(answer))?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Width of each column when `count` answers fit side by side in `width`.
+fn column_width(width: usize, count: usize) -> Option<usize> {
+    if count < 2 {
+        return None;
+    }
+    let separators = COLUMN_SEPARATOR.chars().count() * (count - 1);
+    let column_width = width.saturating_sub(separators) / count;
+    (column_width >= MIN_COLUMN_WIDTH).then_some(column_width)
+}
+
+/// Answers one after the other under a labelled rule.
+fn sections(answers: &[Answer]) -> String {
+    answers
+        .iter()
+        .map(|answer| format!("── {} ──\n{}\n", answer.header(), answer.body()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Answers in columns of `width`, each with its header on top.
+fn columns(answers: &[Answer], width: usize) -> String {
+    let cells: Vec<Vec<String>> = answers
+        .iter()
+        .map(|answer| {
+            let mut lines: Vec<String> = textwrap::wrap(&answer.header(), width)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            lines.push("─".repeat(width));
+            lines.extend(
+                textwrap::wrap(&answer.body(), width)
+                    .into_iter()
+                    .map(String::from),
+            );
+            lines
+        })
+        .collect();
+
+    let rows = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let mut output = String::new();
+    for row in 0..rows {
+        let line = cells
+            .iter()
+            .map(|lines| {
+                let cell = lines.get(row).map(String::as_str).unwrap_or("");
+                let padding = width.saturating_sub(textwrap::core::display_width(cell));
+                format!("{}{}", cell, " ".repeat(padding))
+            })
+            .collect::<Vec<_>>()
+            .join(COLUMN_SEPARATOR);
+        output.push_str(line.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answer(model: &str, content: &str) -> Answer {
+        Answer {
+            model: model.to_string(),
+            latency: Duration::from_millis(1_250),
+            result: Ok(Completion {
+                content: content.to_string(),
+                finish_reason: Some("stop".to_string()),
+                usage: Usage {
+                    prompt_tokens: 30,
+                    completion_tokens: 120,
+                    total_tokens: 150,
+                },
+            }),
+        }
+    }
+
+    #[test]
+    fn checks_profiles_and_prefixed_models() {
+        let profiles = HashMap::from([("local".to_string(), Profile::default())]);
+        let known = vec!["llama3:8b".to_string()];
+        let models = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert!(check_models(
+            &models(&["gpt-4o-mini", "local:llama3", "llama3:8b"]),
+            &profiles,
+            &known
+        )
+        .is_ok());
+        assert!(matches!(
+            check_models(&models(&["remote:llama3"]), &profiles, &known),
+            Err(AskError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn uses_columns_only_when_wide_enough() {
+        assert_eq!(column_width(120, 2), Some(58));
+        assert_eq!(column_width(80, 3), None);
+        assert_eq!(column_width(200, 1), None);
+    }
+
+    #[test]
+    fn labels_sections_and_aligns_columns() {
+        let answers = vec![answer("a", "short"), answer("b", "one two three")];
+        assert!(sections(&answers)
+            .starts_with("── a · 1.2s · 30 prompt + 120 completion tokens ──\nshort\n"));
+
+        let output = columns(&answers, 10);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("a · 1.2s ·"));
+        assert!(lines
+            .iter()
+            .any(|line| line.contains("short") && line.contains("│ one two")));
+    }
+}
@@ -0,0 +1,182 @@
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::AskError;
+use crate::models::tokens::estimate_tokens;
+
+/// Everything from this line on is dropped from the edited question, the same
+/// marker `git commit --verbose` uses.
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// The user's editor: `$VISUAL`, then `$EDITOR`, then a platform default.
+fn editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        })
+}
+
+/// Text shown below the scissors line, with a summary of the piped input.
+fn instructions(stdin: Option<&str>) -> String {
+    let mut text = format!(
+        "{}\n# Write your question above this line, everything below it is ignored.\n\
+         # Save and quit to send it, leave it empty to cancel.\n",
+        SCISSORS
+    );
+    if let Some(stdin) = stdin {
+        let first_line = stdin.lines().find(|line| !line.trim().is_empty());
+        text.push_str(&format!(
+            "#\n# Piped input, sent along with the question: {} lines, ~{} tokens\n",
+            stdin.lines().count(),
+            estimate_tokens(stdin)
+        ));
+        if let Some(line) = first_line {
+            text.push_str(&format!(
+                "#   {}\n",
+                line.chars().take(72).collect::<String>()
+            ));
+        }
+    }
+    text
+}
+
+/// The question in an edited file: the text above the scissors line, trimmed.
+fn question(edited: &str) -> &str {
+    edited
+        .find(SCISSORS)
+        .map_or(edited, |end| &edited[..end])
+        .trim()
+}
+
+/// Creates a new file only the user can read in the temporary directory,
+/// never reusing or following an existing path.
+fn create_temp_file(content: &str) -> Result<PathBuf, AskError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    for attempt in 0..100u32 {
+        let path = env::temp_dir().join(format!(
+            "ask-question-{}-{:x}.md",
+            std::process::id(),
+            nanos.wrapping_add(attempt.wrapping_mul(0x9e37_79b9))
+        ));
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(content.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(AskError::Io(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "Could not create a temporary file for the question",
+    )))
+}
+
+/// Opens the terminal for the editor, so it works even when stdin is piped or
+/// stdout is redirected. Falls back to the inherited stream.
+fn terminal(write: bool) -> Stdio {
+    let path = match (cfg!(windows), write) {
+        (true, false) => "CONIN$",
+        (true, true) => "CONOUT$",
+        (false, _) => "/dev/tty",
+    };
+    OpenOptions::new()
+        .read(!write)
+        .write(write)
+        .open(path)
+        .map_or_else(|_| Stdio::inherit(), Stdio::from)
+}
+
+/// Opens the editor on a temporary file holding `draft` and returns the saved
+/// question. The editor uses the terminal even when stdin or stdout is not one.
+pub fn compose(draft: Option<&str>, stdin: Option<&str>) -> Result<String, AskError> {
+    let path = create_temp_file(&format!(
+        "{}\n\n{}",
+        draft.unwrap_or(""),
+        instructions(stdin)
+    ))?;
+
+    // Through the shell, so an editor with arguments like `code --wait` works
+    let status = if cfg!(windows) {
+        Command::new("cmd")
+            .args(["/C", &format!("{} \"{}\"", editor(), path.display())])
+            .stdin(terminal(false))
+            .stdout(terminal(true))
+            .status()
+    } else {
+        Command::new("sh")
+            .args(["-c", &format!("{} \"$1\"", editor()), "sh"])
+            .arg(&path)
+            .stdin(terminal(false))
+            .stdout(terminal(true))
+            .status()
+    };
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+
+    let status = status?;
+    if !status.success() {
+        return Err(AskError::Usage(format!(
+            "The editor `{}` exited with {}",
+            editor(),
+            status
+        )));
+    }
+    match question(&edited?) {
+        "" => Err(AskError::Cancelled),
+        question => Ok(question.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_text_above_scissors() {
+        let edited = format!(
+            "# Heading\nwhy does this fail?\n\n{}",
+            instructions(Some("error[E0382]: borrow of moved value\n"))
+        );
+        assert_eq!(question(&edited), "# Heading\nwhy does this fail?");
+        assert_eq!(question(&format!("\n\n{}", instructions(None))), "");
+        assert_eq!(question("no marker "), "no marker");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn creates_private_temp_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let first = create_temp_file("draft").unwrap();
+        let second = create_temp_file("draft").unwrap();
+        let mode = std::fs::metadata(&first).unwrap().permissions().mode();
+        let content = std::fs::read_to_string(&first).unwrap();
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(content, "draft");
+    }
+}
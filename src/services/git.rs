@@ -0,0 +1,150 @@
+use cliclack::{note, select};
+use std::process::Command;
+
+use super::api::{complete_all, request_extra, select_model};
+use super::ledger;
+use super::question::RequestOptions;
+use crate::config::setup::Config;
+use crate::error::AskError;
+use crate::models::code::strip_code_fence;
+use crate::models::diff::{fit_files, split_files};
+use crate::models::prompt::{format_commit_question, format_prompt, format_review_question};
+use crate::models::truncate::{Budget, TruncateStrategy};
+
+/// Runs git and returns its output, failing with git's own error message.
+fn git(args: &[&str]) -> Result<String, AskError> {
+    let output = Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        return Err(AskError::Usage(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// What to do with the generated commit message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Confirmation {
+    Commit,
+    Edit,
+    Cancel,
+}
+
+/// Writes a commit message for the staged changes, shows it and commits once
+/// accepted. With `yes` it commits right away.
+pub async fn commit(request: &RequestOptions, yes: bool) -> Result<(), AskError> {
+    let diff = git(&["diff", "--staged"])?;
+    if diff.trim().is_empty() {
+        return Err(AskError::Usage(
+            "Nothing is staged, add changes with `git add` first.".to_string(),
+        ));
+    }
+    let stat = git(&["diff", "--staged", "--stat"])?;
+
+    let config = Config::load_with_role(request.role.as_deref())?;
+    let model = select_model(&config, &request.reasoning);
+    let question = format_commit_question(&stat);
+    let budget = Budget::for_prompt(
+        config.context_size(&model),
+        &config.system_prompt,
+        &question,
+        TruncateStrategy::Head,
+    );
+    budget.ensure_room()?;
+    let prompt = format_prompt(
+        &config.system_prompt,
+        Some(&fit_files(&split_files(&diff), budget.max_tokens)),
+        None,
+        &question,
+        None,
+    );
+
+    let answers = complete_all(
+        &config,
+        model,
+        vec![prompt],
+        &request_extra(&config, &request.extra),
+        "Writing the commit message",
+    )
+    .await?;
+    ledger::check_budget(&config);
+    let message = answers
+        .first()
+        .map(|answer| strip_code_fence(answer).to_string())
+        .unwrap_or_default();
+
+    let confirmation = if yes {
+        Confirmation::Commit
+    } else {
+        note("Commit message", &message)?;
+        select("Commit with this message?")
+            .item(Confirmation::Commit, "Commit", "")
+            .item(Confirmation::Edit, "Edit", "opens git's editor first")
+            .item(Confirmation::Cancel, "Cancel", "")
+            .interact()?
+    };
+
+    let mut command = Command::new("git");
+    command.args(["commit", "-m", &message]);
+    match confirmation {
+        Confirmation::Commit => {}
+        Confirmation::Edit => {
+            command.arg("--edit");
+        }
+        Confirmation::Cancel => return Err(AskError::Cancelled),
+    }
+    if !command.status()?.success() {
+        return Err(AskError::Usage("git commit failed".to_string()));
+    }
+    Ok(())
+}
+
+/// Reviews every file of `git diff <range>` on its own, by default the
+/// uncommitted changes, and prints the comments per file.
+pub async fn review(request: &RequestOptions, range: Option<&str>) -> Result<(), AskError> {
+    let diff = git(&["diff", range.unwrap_or("HEAD")])?;
+    let files = split_files(&diff);
+    if files.is_empty() {
+        return Err(AskError::Usage("No changes to review.".to_string()));
+    }
+
+    let config = Config::load_with_role(request.role.as_deref())?;
+    let model = select_model(&config, &request.reasoning);
+    let prompts = files
+        .iter()
+        .map(|file| {
+            let question = format_review_question(file.path);
+            let budget = Budget::for_prompt(
+                config.context_size(&model),
+                &config.system_prompt,
+                &question,
+                TruncateStrategy::Head,
+            );
+            budget.ensure_room()?;
+            Ok(format_prompt(
+                &config.system_prompt,
+                Some(file.patch),
+                None,
+                &question,
+                Some(&budget),
+            ))
+        })
+        .collect::<Result<_, AskError>>()?;
+
+    let reviews = complete_all(
+        &config,
+        model,
+        prompts,
+        &request_extra(&config, &request.extra),
+        "Reviewing files",
+    )
+    .await?;
+    ledger::check_budget(&config);
+
+    for (file, review) in files.iter().zip(reviews) {
+        println!("── {} ──\n{}\n", file.path, review.trim());
+    }
+    Ok(())
+}
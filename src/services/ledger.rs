@@ -0,0 +1,300 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::api::{print_notice, Notify};
+use super::schema::Usage;
+use crate::config::setup::Config;
+use crate::error::AskError;
+
+/// Share of the monthly budget at which a warning is printed.
+const BUDGET_WARNING_RATIO: f64 = 0.8;
+
+/// One line of the ledger: the token usage of a single request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub timestamp: u64,
+    pub model: String,
+    /// Base URL of the provider the request went to
+    pub provider: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl Record {
+    fn day(&self) -> String {
+        format_time(self.timestamp)[..10].to_string()
+    }
+
+    fn month(&self) -> String {
+        format_time(self.timestamp)[..7].to_string()
+    }
+
+    fn cost(&self, config: &Config) -> Option<f64> {
+        config
+            .price(&self.model)
+            .map(|price| price.cost(self.prompt_tokens, self.completion_tokens))
+    }
+}
+
+/// How `ask usage` groups the ledger.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    Model,
+    Provider,
+    Day,
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "model" => Ok(GroupBy::Model),
+            "provider" => Ok(GroupBy::Provider),
+            "day" => Ok(GroupBy::Day),
+            _ => Err(format!("Invalid grouping: {}", s)),
+        }
+    }
+}
+
+impl GroupBy {
+    fn key(&self, record: &Record) -> String {
+        match self {
+            GroupBy::Model => record.model.clone(),
+            GroupBy::Provider => record.provider.clone(),
+            GroupBy::Day => record.day(),
+        }
+    }
+}
+
+/// Totals of one group in the report.
+#[derive(Debug, Default, PartialEq)]
+pub struct Totals {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// `None` when no request of the group has a known price
+    pub cost: Option<f64>,
+}
+
+impl Totals {
+    fn add(&mut self, record: &Record, config: &Config) {
+        self.requests += 1;
+        self.prompt_tokens += record.prompt_tokens;
+        self.completion_tokens += record.completion_tokens;
+        if let Some(cost) = record.cost(config) {
+            self.cost = Some(self.cost.unwrap_or(0.0) + cost);
+        }
+    }
+}
+
+pub fn get_ledger_path() -> PathBuf {
+    if let Ok(path) = env::var("ASK_USAGE_FILE") {
+        return PathBuf::from(path);
+    }
+    env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+        .map(|dir| dir.join("ask").join("usage.jsonl"))
+        .unwrap_or_else(|_| PathBuf::from("./.ask-usage.jsonl"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn format_time(timestamp: u64) -> String {
+    humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(timestamp)).to_string()
+}
+
+/// Appends the usage of one request. Failures are only reported, a missing
+/// ledger line must not fail the answer.
+pub fn record(config: &Config, model: &str, usage: &Usage) {
+    record_with(config, model, usage, &print_notice)
+}
+
+/// Like [`record`], reporting failures to `notify`.
+pub(crate) fn record_with(config: &Config, model: &str, usage: &Usage, notify: &Notify) {
+    if !config.usage_ledger {
+        return;
+    }
+    let record = Record {
+        timestamp: now(),
+        model: model.to_string(),
+        provider: config.base_url.clone(),
+        prompt_tokens: usage.prompt_tokens.into(),
+        completion_tokens: usage.completion_tokens.into(),
+    };
+    if let Err(e) = append(&record) {
+        notify(&format!("Could not write the usage ledger: {}", e));
+    }
+}
+
+fn append(record: &Record) -> Result<(), AskError> {
+    let path = get_ledger_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Records made at or after `since` (seconds since the epoch). Lines that
+/// cannot be parsed are skipped.
+pub fn read(since: u64) -> Result<Vec<Record>, AskError> {
+    let file = match std::fs::File::open(get_ledger_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(record) = serde_json::from_str::<Record>(&line?) {
+            if record.timestamp >= since {
+                records.push(record);
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Totals per group, sorted by group name.
+pub fn aggregate(records: &[Record], by: GroupBy, config: &Config) -> BTreeMap<String, Totals> {
+    let mut groups: BTreeMap<String, Totals> = BTreeMap::new();
+    for record in records {
+        groups
+            .entry(by.key(record))
+            .or_default()
+            .add(record, config);
+    }
+    groups
+}
+
+fn format_cost(cost: Option<f64>) -> String {
+    cost.map(|cost| format!("${:.4}", cost))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Prints the usage of the last `since` as a table.
+pub fn report(config: &Config, since: Duration, by: GroupBy) -> Result<(), AskError> {
+    let records = read(now().saturating_sub(since.as_secs()))?;
+    if records.is_empty() {
+        println!(
+            "No usage recorded in the last {}.",
+            humantime::format_duration(since)
+        );
+        return Ok(());
+    }
+
+    let groups = aggregate(&records, by, config);
+    let mut total = Totals::default();
+    for record in &records {
+        total.add(record, config);
+    }
+
+    let width = groups.keys().map(String::len).max().unwrap_or(0).max(5);
+    println!(
+        "{:<width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+        "",
+        "requests",
+        "prompt",
+        "completion",
+        "cost",
+        width = width
+    );
+    let total_name = "total".to_string();
+    for (name, totals) in groups.iter().chain([(&total_name, &total)]) {
+        println!(
+            "{:<width$}  {:>8}  {:>12}  {:>12}  {:>10}",
+            name,
+            totals.requests,
+            totals.prompt_tokens,
+            totals.completion_tokens,
+            format_cost(totals.cost),
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Warns on stderr when the estimated spend of the current month nears or
+/// exceeds `monthly_budget`.
+pub fn check_budget(config: &Config) {
+    let Some(budget) = config.monthly_budget else {
+        return;
+    };
+    let this_month = format_time(now())[..7].to_string();
+    // A month is at most 31 days, older records cannot be in it
+    let records = match read(now().saturating_sub(31 * 86_400)) {
+        Ok(records) => records,
+        Err(_) => return,
+    };
+    let spent: f64 = records
+        .iter()
+        .filter(|record| record.month() == this_month)
+        .filter_map(|record| record.cost(config))
+        .sum();
+
+    if spent >= budget {
+        eprintln!(
+            "Warning: estimated spend this month is ${:.2}, over the monthly budget of ${:.2}",
+            spent, budget
+        );
+    } else if spent >= budget * BUDGET_WARNING_RATIO {
+        eprintln!(
+            "Warning: estimated spend this month is ${:.2}, {:.0}% of the monthly budget of ${:.2}",
+            spent,
+            spent / budget * 100.0,
+            budget
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(model: &str, timestamp: u64, prompt_tokens: u64) -> Record {
+        Record {
+            timestamp,
+            model: model.to_string(),
+            provider: "https://api.example.com/v1".to_string(),
+            prompt_tokens,
+            completion_tokens: 100,
+        }
+    }
+
+    #[test]
+    fn aggregates_by_model_and_day() {
+        let config = Config::default();
+        let records = vec![
+            record("gpt-4o-mini", 0, 1_000),
+            record("gpt-4o-mini", 90_000, 2_000),
+            record("local-model", 90_000, 500),
+        ];
+
+        let by_model = aggregate(&records, GroupBy::Model, &config);
+        let mini = &by_model["gpt-4o-mini"];
+        assert_eq!(mini.requests, 2);
+        assert_eq!(mini.prompt_tokens, 3_000);
+        assert!(mini.cost.is_some());
+        assert_eq!(by_model["local-model"].cost, None);
+
+        let by_day = aggregate(&records, GroupBy::Day, &config);
+        assert_eq!(
+            by_day.keys().collect::<Vec<_>>(),
+            vec!["1970-01-01", "1970-01-02"]
+        );
+    }
+}
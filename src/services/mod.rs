@@ -1,6 +1,19 @@
 pub(super) mod additional_config;
 pub mod api;
-pub(super) mod request;
+pub mod api_error;
+pub mod cache;
+pub(super) mod client;
+pub(super) mod clipboard;
+pub(super) mod compare;
+pub mod editor;
+pub mod git;
+pub mod ledger;
+pub mod output;
+pub mod question;
+pub mod request;
+pub(super) mod retry;
 pub mod schema;
+pub mod shell;
 pub(super) mod spinner;
-pub(super) mod stream;
+pub mod stream;
+pub mod structured;
@@ -0,0 +1,315 @@
+use super::clipboard;
+use super::schema::Usage;
+use crate::error::AskError;
+use crate::models::code::{extract_code_blocks, CodeBlock, Selector};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Print the answer as plain text
+    Text,
+    /// Print a single JSON envelope once the answer is complete
+    Json,
+    /// Print one JSON event per line while the answer arrives
+    Ndjson,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => Err(format!("Invalid output format: {}", s)),
+        }
+    }
+}
+
+/// Everything known about one answer, printed by `--output json`.
+#[derive(Debug, Serialize)]
+pub struct Envelope<'a> {
+    pub model: &'a str,
+    pub content: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<&'a str>,
+    pub finish_reason: Option<&'a str>,
+    pub usage: Option<&'a Usage>,
+    pub latency_ms: u128,
+}
+
+/// Events printed one per line by `--output ndjson`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    Delta {
+        content: &'a str,
+    },
+    Reasoning {
+        content: &'a str,
+    },
+    Usage {
+        usage: &'a Usage,
+    },
+    Done {
+        model: &'a str,
+        finish_reason: Option<&'a str>,
+        latency_ms: u128,
+    },
+    Error {
+        message: &'a str,
+    },
+}
+
+/// What to do with the fenced code blocks of the answer.
+#[derive(Debug, Clone)]
+pub struct CodeOptions {
+    /// Print only the code blocks instead of the whole answer
+    pub print_only: bool,
+    pub selector: Selector,
+    pub save_dir: Option<PathBuf>,
+    pub copy: bool,
+}
+
+impl CodeOptions {
+    /// Prints, saves and copies the selected blocks of `answer`.
+    fn apply(&self, answer: &str) -> Result<(), AskError> {
+        let blocks = self.selector.select(extract_code_blocks(answer));
+        if blocks.is_empty() && self.print_only {
+            return Err(AskError::Parse(
+                "No matching code blocks in the answer".to_string(),
+            ));
+        }
+
+        let joined = blocks
+            .iter()
+            .map(|block| block.code.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        if self.print_only {
+            println!("{}", joined);
+        }
+        if let Some(dir) = &self.save_dir {
+            save_blocks(dir, &blocks)?;
+        }
+        if self.copy {
+            if blocks.is_empty() {
+                clipboard::copy(answer.trim())?;
+            } else {
+                clipboard::copy(&joined)?;
+            }
+            eprintln!("Copied to clipboard.");
+        }
+        Ok(())
+    }
+}
+
+fn save_blocks(dir: &Path, blocks: &[CodeBlock]) -> Result<(), AskError> {
+    std::fs::create_dir_all(dir)?;
+    for (index, block) in blocks.iter().enumerate() {
+        let path = dir.join(format!("snippet-{}.{}", index + 1, block.extension()));
+        std::fs::write(&path, format!("{}\n", block.code))?;
+        eprintln!("Saved {}", path.display());
+    }
+    Ok(())
+}
+
+/// Receives the answer as it arrives and prints it in the requested format.
+pub struct Printer {
+    format: OutputFormat,
+    verbose: bool,
+    model: String,
+    started: Instant,
+    content: String,
+    reasoning: String,
+    finish_reason: Option<String>,
+    usage: Option<Usage>,
+    code: Option<CodeOptions>,
+}
+
+impl Printer {
+    pub fn new(format: OutputFormat, verbose: bool, model: String) -> Self {
+        Self {
+            format,
+            verbose,
+            model,
+            started: Instant::now(),
+            content: String::new(),
+            reasoning: String::new(),
+            finish_reason: None,
+            usage: None,
+            code: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: Option<CodeOptions>) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Whether the answer is printed as it arrives in text mode.
+    fn prints_text(&self) -> bool {
+        !self.code.as_ref().is_some_and(|code| code.print_only)
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn reasoning_text(&self) -> &str {
+        &self.reasoning
+    }
+
+    pub fn finish_reason_text(&self) -> Option<&str> {
+        self.finish_reason.as_deref()
+    }
+
+    pub fn usage_info(&self) -> Option<&Usage> {
+        self.usage.as_ref()
+    }
+
+    fn emit(&self, event: &Event) -> Result<(), AskError> {
+        println!("{}", serde_json::to_string(event)?);
+        Ok(())
+    }
+
+    pub fn delta(&mut self, content: &str) -> Result<(), AskError> {
+        match self.format {
+            OutputFormat::Text if self.prints_text() => {
+                print!("{}", content);
+                // Flush immediately to show the output
+                std::io::stdout().flush()?;
+            }
+            OutputFormat::Ndjson => self.emit(&Event::Delta { content })?,
+            _ => {}
+        }
+        self.content.push_str(content);
+        Ok(())
+    }
+
+    pub fn reasoning(&mut self, content: &str) -> Result<(), AskError> {
+        match self.format {
+            OutputFormat::Text if self.verbose => {
+                eprint!("{}", content);
+            }
+            OutputFormat::Ndjson => self.emit(&Event::Reasoning { content })?,
+            _ => {}
+        }
+        self.reasoning.push_str(content);
+        Ok(())
+    }
+
+    pub fn finish_reason(&mut self, finish_reason: String) {
+        self.finish_reason = Some(finish_reason);
+    }
+
+    pub fn usage(&mut self, usage: Usage) {
+        self.usage = Some(usage);
+    }
+
+    /// Prints what is left once the answer is complete: the usage line in
+    /// verbose text mode, the envelope, or the closing events.
+    pub fn finish(&self) -> Result<(), AskError> {
+        self.print_end()?;
+        match self.finish_reason.as_deref() {
+            Some("content_filter" | "SAFETY") => Err(AskError::ContentFiltered),
+            _ => Ok(()),
+        }
+    }
+
+    fn print_end(&self) -> Result<(), AskError> {
+        let latency_ms = self.started.elapsed().as_millis();
+        match self.format {
+            OutputFormat::Text => {
+                if self.prints_text() && !self.content.ends_with('\n') {
+                    println!();
+                }
+                if let Some(code) = &self.code {
+                    code.apply(&self.content)?;
+                }
+                if self.verbose {
+                    if let Some(usage_info) = &self.usage {
+                        println!(
+                            "\nUsage: prompt_tokens: {}, completion_tokens: {}, total_tokens: {}",
+                            usage_info.prompt_tokens,
+                            usage_info.completion_tokens,
+                            usage_info.total_tokens
+                        );
+                    } else {
+                        println!("No usage information available.");
+                    }
+                }
+            }
+            OutputFormat::Json => {
+                let envelope = Envelope {
+                    model: &self.model,
+                    content: &self.content,
+                    reasoning: (!self.reasoning.is_empty()).then_some(self.reasoning.as_str()),
+                    finish_reason: self.finish_reason.as_deref(),
+                    usage: self.usage.as_ref(),
+                    latency_ms,
+                };
+                println!("{}", serde_json::to_string(&envelope)?);
+            }
+            OutputFormat::Ndjson => {
+                if let Some(usage) = &self.usage {
+                    self.emit(&Event::Usage { usage })?;
+                }
+                self.emit(&Event::Done {
+                    model: &self.model,
+                    finish_reason: self.finish_reason.as_deref(),
+                    latency_ms,
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports a failure as an event in NDJSON mode. Other formats leave the
+    /// error to the caller.
+    pub fn error(&self, message: &str) {
+        if self.format == OutputFormat::Ndjson {
+            if let Ok(line) = serde_json::to_string(&Event::Error { message }) {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_events_with_type_tag() {
+        let event = Event::Delta { content: "hi" };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"delta","content":"hi"}"#
+        );
+
+        let event = Event::Done {
+            model: "m",
+            finish_reason: Some("stop"),
+            latency_ms: 5,
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"done","model":"m","finish_reason":"stop","latency_ms":5}"#
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use serde_json::Value;
+use std::path::PathBuf;
+
+use super::api;
+use super::editor;
+use super::output::{CodeOptions, OutputFormat};
+use super::request::ReasoningEffort;
+use super::shell::{self, LastCommand};
+use crate::config::setup::Config;
+use crate::config::templates;
+use crate::error::AskError;
+use crate::models::prompt;
+use crate::models::tokens::estimate_tokens;
+use crate::models::truncate::{Budget, TruncateStrategy};
+
+/// Options of every request: the role, the reasoning effort and body patches.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOptions {
+    /// Role from the configuration, else `default_role`
+    pub role: Option<String>,
+    pub reasoning: ReasoningEffort,
+    /// JSON objects deep merged into the request body, in order
+    pub extra: Vec<Value>,
+}
+
+/// What is done with the prompt.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Mode {
+    /// Print the answer, streamed when the configuration says so
+    #[default]
+    Chat,
+    /// Print only a shell command, for the shell widget
+    Command,
+    /// Ask every model and print the answers side by side
+    Compare(Vec<String>),
+    /// Print a JSON answer, validated against the schema file if given
+    Json(Option<PathBuf>),
+}
+
+/// How a question is asked, the options of `ask <QUESTION>`.
+#[derive(Debug, Clone, Default)]
+pub struct AskOptions {
+    pub request: RequestOptions,
+    pub mode: Mode,
+    /// Show reasoning and token usage
+    pub verbose: bool,
+    /// `None` for `output` of the configuration, else text
+    pub output: Option<OutputFormat>,
+    pub code: Option<CodeOptions>,
+    pub no_cache: bool,
+    pub refresh: bool,
+    /// How piped input over the context window is shrunk
+    pub truncate: TruncateStrategy,
+    /// Split piped input over the context window into chunks instead
+    pub chunked: bool,
+    /// Heading of the piped input instead of STDIN
+    pub stdin_label: Option<String>,
+    /// Name of the template the prompt is built from
+    pub template: Option<String>,
+}
+
+impl AskOptions {
+    pub fn output(&self) -> OutputFormat {
+        self.output.unwrap_or(OutputFormat::Text)
+    }
+}
+
+/// The question and what comes with it.
+#[derive(Debug, Default)]
+pub struct Question {
+    /// Words of the question. A `-` word is replaced with the piped input
+    pub words: Vec<String>,
+    pub stdin: Option<String>,
+    /// The failed command to explain, whose stderr takes the place of `stdin`
+    pub last_command: Option<LastCommand>,
+    /// Write the question in the editor, even when words are given
+    pub editor: bool,
+}
+
+impl Question {
+    /// The words of the question joined with spaces, `None` if there are none.
+    pub fn text(&self) -> Option<String> {
+        (!self.words.is_empty()).then(|| self.words.join(" "))
+    }
+
+    /// Whether a `-` word places the piped input inside the question.
+    pub fn places_stdin(&self) -> bool {
+        self.words.iter().any(|word| word == "-")
+    }
+
+    /// The question with every `-` word replaced by `stdin`.
+    pub fn text_with_stdin(&self, stdin: &str) -> String {
+        self.words
+            .iter()
+            .map(|word| if word == "-" { stdin.trim_end() } else { word })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Asks `question` and prints the answer: builds the prompt within the
+/// context window of the model, then sends it the way `options.mode` says.
+pub async fn ask(question: Question, mut options: AskOptions) -> Result<(), AskError> {
+    let config = Config::load_with_role(options.request.role.as_deref())?;
+    options.output = options.output.or(config.output);
+    // Code blocks are only picked out of text answers
+    if options.output() != OutputFormat::Text && options.code.is_some() {
+        return Err(AskError::Usage(
+            "--code, --extract, --save-code and --copy only work with `-o text`".to_string(),
+        ));
+    }
+    let template = options
+        .template
+        .as_deref()
+        .map(|name| templates::load(&config, name))
+        .transpose()?;
+
+    let model = api::select_model(&config, &options.request.reasoning);
+    // Compared models share one prompt, so it has to fit the smallest window
+    let context_size = match &options.mode {
+        Mode::Compare(entries) => entries
+            .iter()
+            .map(|entry| {
+                let model = config
+                    .profile(entry)
+                    .map_or(entry.as_str(), |(_, model)| model);
+                config.context_size(model)
+            })
+            .min()
+            .unwrap_or_else(|| config.context_size(&model)),
+        _ => config.context_size(&model),
+    };
+
+    // Piped input alone is the question, and a `-` word places it in the question
+    if options.stdin_label.is_some() && question.places_stdin() {
+        return Err(AskError::Usage(
+            "--stdin-label cannot be used with `-`, which puts the piped input in the question"
+                .to_string(),
+        ));
+    }
+    let inline_stdin = question.last_command.is_none()
+        && options.stdin_label.is_none()
+        && question.stdin.as_deref().is_some_and(|stdin| {
+            question.places_stdin()
+                || (question.words.is_empty()
+                    && options.template.is_none()
+                    && !question.editor
+                    && !stdin.trim().is_empty())
+        });
+    let (input, stdin_content) = match &question.stdin {
+        Some(stdin) if inline_stdin => {
+            // The input still has to fit next to the rest of the question
+            let budget = Budget::for_prompt(
+                context_size,
+                &config.system_prompt,
+                &format!(
+                    "{}{}",
+                    template.as_deref().unwrap_or(""),
+                    question.text().unwrap_or_default()
+                ),
+                options.truncate,
+            );
+            budget.ensure_room()?;
+            let stdin = if budget.fits(stdin) {
+                stdin.clone()
+            } else if options.chunked {
+                return Err(AskError::Usage(
+                    "The piped input is over the context window and --chunked cannot split a \
+                     question, pass the question as arguments instead"
+                        .to_string(),
+                ));
+            } else if options.truncate == TruncateStrategy::Summarize {
+                eprintln!(
+                    "Input is ~{} tokens, over the budget of {} tokens. Summarizing it first.",
+                    estimate_tokens(stdin),
+                    budget.max_tokens
+                );
+                api::summarize(stdin, &budget, &options.request).await?
+            } else {
+                prompt::fit_to_budget(stdin, Some(&budget))
+            };
+            let input = if question.places_stdin() {
+                question.text_with_stdin(&stdin)
+            } else {
+                stdin.trim().to_string()
+            };
+            (Some(input), None)
+        }
+        stdin => (question.text(), stdin.clone()),
+    };
+
+    // Without any question or input, or with -e, it is written in the editor
+    let input = if question.editor
+        || (input.is_none()
+            && options.template.is_none()
+            && question.last_command.is_none()
+            && stdin_content.is_none())
+    {
+        Some(editor::compose(input.as_deref(), stdin_content.as_deref())?)
+    } else {
+        input
+    };
+
+    // A template or --explain-last can make do without a question
+    let user_question = match (&question.last_command, &input, &options.template) {
+        (Some(last), input, _) => prompt::format_explain_question(
+            &last.command,
+            last.status,
+            last.cwd.as_deref(),
+            last.stderr.is_some(),
+            input.as_deref(),
+        ),
+        (None, Some(input), _) if options.mode == Mode::Command => {
+            prompt::format_command_question(input, &shell::current_shell(), std::env::consts::OS)
+        }
+        (None, Some(input), _) => input.clone(),
+        (None, None, Some(_)) => String::new(),
+        (None, None, None) => {
+            return Err(AskError::Usage("Please enter your question.".to_string()))
+        }
+    };
+    let user_question = user_question.as_str();
+
+    let budget = Budget::for_prompt(
+        context_size,
+        &config.system_prompt,
+        &format!("{}{}", template.as_deref().unwrap_or(""), user_question),
+        options.truncate,
+    );
+    if stdin_content.is_some() {
+        budget.ensure_room()?;
+    }
+
+    let mut question = user_question.to_string();
+    let mut stdin_label = options.stdin_label.as_deref();
+    let stdin_content = match stdin_content {
+        Some(stdin) if options.chunked && !budget.fits(&stdin) => {
+            let partials = api::map_chunks(
+                &stdin,
+                user_question,
+                &budget,
+                options.stdin_label.as_deref(),
+                &options.request,
+            )
+            .await?;
+            question = prompt::format_reduce_question(user_question);
+            // The reduce step gets the partial answers, not the labelled input
+            stdin_label = None;
+            Some(prompt::format_partial_answers(&partials))
+        }
+        Some(stdin) if options.truncate == TruncateStrategy::Summarize && !budget.fits(&stdin) => {
+            eprintln!(
+                "Input is ~{} tokens, over the budget of {} tokens. Summarizing it first.",
+                estimate_tokens(&stdin),
+                budget.max_tokens
+            );
+            Some(api::summarize(&stdin, &budget, &options.request).await?)
+        }
+        other => other,
+    };
+
+    let prompt = match &template {
+        Some(template) => prompt::format_template_prompt(
+            &config.system_prompt,
+            template,
+            stdin_content.as_deref(),
+            &question,
+            Some(&budget),
+        )?,
+        None => prompt::format_prompt(
+            &config.system_prompt,
+            stdin_content.as_deref(),
+            stdin_label,
+            &question,
+            Some(&budget),
+        ),
+    };
+
+    match &options.mode {
+        Mode::Chat => api::chat(prompt, &options).await,
+        Mode::Command => api::command(prompt, &options.request).await,
+        Mode::Compare(entries) => {
+            api::compare(prompt, entries, &options.request, options.output()).await
+        }
+        Mode::Json(schema) => api::chat_json(prompt, schema.as_deref(), &options.request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(words: &[&str]) -> Question {
+        Question {
+            words: words.iter().map(|word| word.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn places_stdin_at_dash_words() {
+        let explain = question(&["explain", "-", "briefly"]);
+        assert!(explain.places_stdin());
+        assert_eq!(
+            explain.text_with_stdin("error[E0382]\n"),
+            "explain error[E0382] briefly"
+        );
+
+        let quoted = question(&["what does - mean"]);
+        assert!(!quoted.places_stdin());
+        assert_eq!(quoted.text_with_stdin("ignored"), "what does - mean");
+    }
+}
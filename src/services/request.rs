@@ -1,6 +1,7 @@
 use super::additional_config::gemini_config;
 use super::schema::Message;
 use serde::Serialize;
+use serde_json::Value;
 
 #[derive(Serialize, Debug)]
 pub struct RequestBody {
@@ -14,12 +15,60 @@ pub struct RequestBody {
     #[serde(skip_serializing_if = "Option::is_none")]
     reasoning_effort: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+
+    // Gemini reads its vendor options from a literal `extra_body` field
+    #[serde(skip_serializing_if = "is_empty_object")]
     extra_body: serde_json::Value,
+
+    // Patches deep merged into the top level of the body, in order
+    #[serde(skip)]
+    extra: Vec<serde_json::Value>,
 }
 impl RequestBody {
     pub fn builder() -> RequestBodyBuilder {
         RequestBodyBuilder::new()
     }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.stream
+    }
+
+    /// The JSON sent to the API, with the `extra` patches merged into the top
+    /// level in order, so a `null` can also remove a field set by the body.
+    pub fn to_json(&self) -> Value {
+        let mut body = serde_json::json!(self);
+        for patch in &self.extra {
+            deep_merge(&mut body, patch);
+        }
+        body
+    }
+}
+
+fn is_empty_object(value: &Value) -> bool {
+    value.as_object().is_some_and(|map| map.is_empty())
+}
+
+/// Merges `patch` into `base` like a JSON merge patch (RFC 7396): objects are
+/// merged key by key, `null` removes a key and anything else replaces it.
+pub fn deep_merge(base: &mut Value, patch: &Value) {
+    match (base, patch) {
+        (Value::Object(base), Value::Object(patch)) => {
+            for (key, value) in patch {
+                if value.is_null() {
+                    base.remove(key);
+                } else {
+                    deep_merge(base.entry(key.clone()).or_insert(Value::Null), value);
+                }
+            }
+        }
+        (base, patch) => *base = patch.clone(),
+    }
 }
 
 #[derive(Default, Clone)]
@@ -30,6 +79,8 @@ pub struct RequestBodyBuilder {
     pub(super) reasoning_effort: Option<String>,
     pub(super) show_reasoning: bool,
     pub(super) extra_body: Option<serde_json::Value>,
+    pub(super) response_format: Option<serde_json::Value>,
+    pub(super) extra: Vec<serde_json::Value>,
 }
 
 impl RequestBodyBuilder {
@@ -56,10 +107,24 @@ impl RequestBodyBuilder {
         self.reasoning_effort = reasoning_effort.as_option_string();
         self
     }
-    // This is not used yet, just a placeholder for future use
-    #[allow(dead_code)]
-    pub fn extra_body(mut self, extra_body: serde_json::Value) -> Self {
-        self.extra_body = Some(extra_body);
+    /// Provider specific fields merged into the top level of the body, like
+    /// `top_k` or `safety_settings`. Repeated calls are merged in order.
+    pub fn extra(mut self, extra: &Value) -> Self {
+        if !extra.is_null() {
+            self.extra.push(extra.clone());
+        }
+        self
+    }
+
+    /// Calls [`RequestBodyBuilder::extra`] with every patch in order.
+    pub fn extras(self, patches: &[Value]) -> Self {
+        patches
+            .iter()
+            .fold(self, |builder, patch| builder.extra(patch))
+    }
+
+    pub fn response_format(mut self, response_format: serde_json::Value) -> Self {
+        self.response_format = Some(response_format);
         self
     }
 
@@ -102,14 +167,17 @@ impl RequestBodyBuilder {
             stream,
             stream_options,
             reasoning_effort,
+            response_format: builder.response_format,
             extra_body,
+            extra: builder.extra,
         })
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub enum ReasoningEffort {
     /// No reasoning (0)
+    #[default]
     None = 0,
     /// Low reasoning effort (1)
     Low = 1,
@@ -152,10 +220,7 @@ mod tests {
 
     #[test]
     fn test_gemini_model_validation() {
-        let messages = vec![Message {
-            role: Some("user".to_string()),
-            content: Some("Hello".to_string()),
-        }];
+        let messages = vec![Message::new("user", "Hello".to_string())];
 
         let request = RequestBodyBuilder::new()
             .model("gemini-pro".to_string())
@@ -224,4 +289,42 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn merges_extra_into_top_level() {
+        let request = RequestBodyBuilder::new()
+            .model("gpt-4o-mini".to_string())
+            .messages(vec![Message::new("user", "Hello".to_string())])
+            .stream(true)
+            .extra(&json!({"top_k": 40, "stream_options": {"foo": 1}}))
+            .extra(&json!({"repetition_penalty": 1.1, "top_k": null}))
+            .build()
+            .unwrap();
+
+        let body = request.to_json();
+        assert!(body.get("extra_body").is_none());
+        assert!(body.get("top_k").is_none());
+        assert_eq!(body["repetition_penalty"], json!(1.1));
+        assert_eq!(
+            body["stream_options"],
+            json!({"include_usage": true, "foo": 1})
+        );
+        assert_eq!(body["model"], json!("gpt-4o-mini"));
+    }
+
+    #[test]
+    fn extra_null_removes_body_field() {
+        let request = RequestBodyBuilder::new()
+            .model("gpt-4o-mini".to_string())
+            .messages(vec![Message::new("user", "Hello".to_string())])
+            .stream(true)
+            .extras(&[json!({"temperature": 0.2}), json!({"stream_options": null})])
+            .build()
+            .unwrap();
+
+        let body = request.to_json();
+        assert!(body.get("stream_options").is_none());
+        assert_eq!(body["stream"], json!(true));
+        assert_eq!(body["temperature"], json!(0.2));
+    }
 }
@@ -0,0 +1,119 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+use crate::config::setup::Config;
+
+/// When and how long to wait before sending a failed request again.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.retry_max_delay_ms),
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0). A `Retry-After`
+    /// from the server wins, otherwise the delay doubles every attempt with
+    /// up to 50% random jitter. Both are capped at `max_delay`.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let jitter = exponential.mul_f64(jitter_fraction() * 0.5);
+        (exponential + jitter).min(self.max_delay)
+    }
+}
+
+/// Rate limits and server errors are worth retrying, client errors are not.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Connection failures happen before the request was sent. A timeout may hit
+/// a request the provider is still working on, which is only worth sending
+/// again when streaming, where it means not even the headers arrived.
+pub fn is_retryable_error(error: &reqwest::Error, stream: bool) -> bool {
+    error.is_connect() || (stream && error.is_timeout())
+}
+
+/// Reads `Retry-After` as either a number of seconds or an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// Random-enough number in [0, 1) for jitter, without pulling in a RNG. Every
+/// `RandomState` is keyed differently, so the hash of nothing is random.
+fn jitter_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    bits as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_within_cap() {
+        let policy = policy();
+        for attempt in 0..3 {
+            let base = Duration::from_millis(500 * 2u64.pow(attempt));
+            let delay = policy.delay(attempt, None);
+            assert!(delay >= base && delay <= base.mul_f64(1.5));
+        }
+        assert_eq!(policy.delay(20, None), Duration::from_secs(10));
+
+        let delays: Vec<_> = (0..8).map(|_| policy.delay(1, None)).collect();
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+        let retry_after = retry_after(&headers);
+        assert_eq!(retry_after, Some(Duration::from_secs(7)));
+        assert_eq!(policy().delay(0, retry_after), Duration::from_secs(7));
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(super::retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retries_rate_limits_and_server_errors_only() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+    }
+}
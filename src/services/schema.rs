@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AskError;
 
 #[derive(Debug, Deserialize)]
 pub struct Response {
@@ -8,6 +11,7 @@ pub struct Response {
 #[derive(Debug, Deserialize)]
 pub struct Choice {
     pub delta: Message,
+    pub finish_reason: Option<String>,
 }
 
 //TODO: Refactor the non streaming and streaming responses so they can be shared
@@ -20,9 +24,10 @@ pub struct NonStreamingResponse {
 #[derive(Debug, Deserialize)]
 pub struct NonStreamingChoice {
     pub message: Message,
+    pub finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
@@ -33,14 +38,104 @@ pub struct Usage {
 pub struct Message {
     pub role: Option<String>,
     pub content: Option<String>,
+    /// Reasoning text, sent as `reasoning_content` or `reasoning` depending on the provider
+    #[serde(default, alias = "reasoning", skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Message {
+    pub fn new(role: &str, content: String) -> Self {
+        Self {
+            role: Some(role.to_string()),
+            content: Some(content),
+            reasoning_content: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
 pub struct Model {
     pub id: String,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+    /// Context window in tokens, when the provider reports it
+    #[serde(default)]
+    pub context_length: Option<u64>,
+}
+
+impl Model {
+    /// Reads a model entry from any of the common `/models` formats. Gemini
+    /// prefixes ids with `models/`, which the chat endpoint does not expect.
+    fn from_value(value: &Value) -> Option<Self> {
+        let first_str = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| value.get(key).and_then(Value::as_str))
+                .map(str::to_string)
+        };
+        let id = first_str(&["id", "name", "model"])?;
+        Some(Self {
+            id: id.strip_prefix("models/").unwrap_or(&id).to_string(),
+            owned_by: first_str(&["owned_by", "owner", "organization"]),
+            context_length: [
+                "context_length",
+                "context_window",
+                "max_context_length",
+                "max_model_len",
+                "inputTokenLimit",
+            ]
+            .iter()
+            .find_map(|key| value.get(key).and_then(Value::as_u64)),
+        })
+    }
 }
 
 #[derive(Deserialize)]
 pub struct APIResponse {
     pub data: Vec<Model>,
 }
+
+impl APIResponse {
+    /// Parses a `/models` response: an OpenAI style `data` list, a `models`
+    /// list (Gemini, Ollama) or a bare array. Entries without an id are skipped.
+    pub fn parse(body: &str) -> Result<Self, AskError> {
+        let value: Value = serde_json::from_str(body)?;
+        let entries = match &value {
+            Value::Array(entries) => entries,
+            _ => ["data", "models"]
+                .iter()
+                .find_map(|key| value.get(key).and_then(Value::as_array))
+                .ok_or_else(|| {
+                    AskError::Parse("Unrecognized format of the models list".to_string())
+                })?,
+        };
+        let mut data: Vec<Model> = entries.iter().filter_map(Model::from_value).collect();
+        data.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(Self { data })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_models_list_formats() {
+        let openrouter =
+            r#"{"data": [{"id": "b", "name": "B", "context_length": 8192}, {"id": "a"}]}"#;
+        let parsed = APIResponse::parse(openrouter).unwrap();
+        assert_eq!(parsed.data[0].id, "a");
+        assert_eq!(parsed.data[1].context_length, Some(8192));
+
+        let gemini =
+            r#"{"models": [{"name": "models/gemini-2.0-flash", "inputTokenLimit": 1048576}]}"#;
+        let parsed = APIResponse::parse(gemini).unwrap();
+        assert_eq!(parsed.data[0].id, "gemini-2.0-flash");
+        assert_eq!(parsed.data[0].context_length, Some(1_048_576));
+
+        let bare = r#"[{"id": "llama3", "owned_by": "library"}]"#;
+        let parsed = APIResponse::parse(bare).unwrap();
+        assert_eq!(parsed.data[0].owned_by.as_deref(), Some("library"));
+
+        assert!(APIResponse::parse(r#"{"error": "nope"}"#).is_err());
+    }
+}
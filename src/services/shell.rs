@@ -0,0 +1,134 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use crate::error::AskError;
+
+/// Shells with an integration script.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl std::str::FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("Unsupported shell: {}", s)),
+        }
+    }
+}
+
+impl Shell {
+    /// The integration script printed by `ask shell-init`.
+    pub fn init_script(&self) -> &'static str {
+        match self {
+            Shell::Bash => include_str!("../../shell/ask.bash"),
+            Shell::Zsh => include_str!("../../shell/ask.zsh"),
+            Shell::Fish => include_str!("../../shell/ask.fish"),
+        }
+    }
+}
+
+/// Name of the user's shell from `$SHELL`, used to ask for commands in its syntax.
+pub fn current_shell() -> String {
+    env::var("SHELL")
+        .ok()
+        .and_then(|path| {
+            Path::new(&path)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| {
+            if cfg!(windows) {
+                "powershell".to_string()
+            } else {
+                "sh".to_string()
+            }
+        })
+}
+
+/// The last failed command, as recorded by the shell integration scripts.
+#[derive(Debug, PartialEq)]
+pub struct LastCommand {
+    pub command: String,
+    pub status: i32,
+    pub cwd: Option<String>,
+    /// `None` when stderr was not captured or was empty
+    pub stderr: Option<String>,
+}
+
+/// Directory the shell integration writes to. Must match the scripts.
+pub fn get_state_dir() -> PathBuf {
+    if let Ok(dir) = env::var("ASK_STATE_DIR") {
+        return PathBuf::from(dir);
+    }
+    env::var("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))
+        .map(|dir| dir.join("ask"))
+        .unwrap_or_else(|_| PathBuf::from("./.ask-state"))
+}
+
+fn read_trimmed(dir: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(dir.join(name))
+        .ok()
+        .map(|content| content.trim_end().to_string())
+        .filter(|content| !content.is_empty())
+}
+
+pub fn last_command() -> Result<LastCommand, AskError> {
+    read_last_command(&get_state_dir())
+}
+
+fn read_last_command(dir: &Path) -> Result<LastCommand, AskError> {
+    let command = read_trimmed(dir, "command").ok_or_else(|| {
+        AskError::Usage(format!(
+            "No failed command recorded in {}. Load the shell integration first, see the README.",
+            dir.display()
+        ))
+    })?;
+    let status = read_trimmed(dir, "status")
+        .and_then(|status| status.parse().ok())
+        .unwrap_or(1);
+    Ok(LastCommand {
+        command,
+        status,
+        cwd: read_trimmed(dir, "cwd"),
+        stderr: read_trimmed(dir, "stderr"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_recorded_command() {
+        let dir = env::temp_dir().join(format!("ask-shell-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(read_last_command(&dir).is_err());
+
+        std::fs::write(dir.join("command"), "cargo biuld\n").unwrap();
+        std::fs::write(dir.join("status"), "101\n").unwrap();
+        std::fs::write(dir.join("stderr"), "").unwrap();
+        let last = read_last_command(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            last,
+            LastCommand {
+                command: "cargo biuld".to_string(),
+                status: 101,
+                cwd: None,
+                stderr: None,
+            }
+        );
+    }
+}
@@ -56,6 +56,33 @@ impl Spinner {
     }
 }
 
+/// Progress bar for a known number of requests, such as the chunks in chunked mode.
+pub struct Progress {
+    progress_bar: ProgressBar,
+}
+
+impl Progress {
+    pub fn new(message: &str, len: usize) -> Self {
+        let pb = ProgressBar::new(len as u64);
+        pb.enable_steady_tick(Duration::from_millis(100));
+        pb.set_style(
+            ProgressStyle::with_template("{msg:.cyan} [{bar:30.cyan/blue}] {pos}/{len} {elapsed}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
+        pb.set_message(message.to_string());
+        Self { progress_bar: pb }
+    }
+
+    pub fn inc(&self) {
+        self.progress_bar.inc(1);
+    }
+
+    pub fn finish(self) {
+        self.progress_bar.finish_and_clear();
+    }
+}
+
 pub fn create_reasoning_spinner() -> Spinner {
     Spinner::new("Thinking")
 }
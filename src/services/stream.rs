@@ -1,54 +1,228 @@
-use super::schema::Response;
-use super::schema::Usage;
-use futures_util::StreamExt;
-use std::io::Write;
+use super::output::Printer;
+use super::schema::{Response, Usage};
+use crate::error::AskError;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// One piece of a streamed answer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Text of the answer
+    Delta(String),
+    /// Reasoning text, from models and providers that send it
+    Reasoning(String),
+    /// Why the model stopped, e.g. `stop` or `length`
+    FinishReason(String),
+    /// Token usage, usually sent once at the end
+    Usage(Usage),
+}
+
+/// Events of a streamed answer, in order. Ends after `data: [DONE]` or when
+/// the connection closes, and fails with [`AskError::StreamStalled`] when no
+/// data arrives for `idle_timeout`.
+pub type EventStream = BoxStream<'static, Result<Event, AskError>>;
+
+struct State {
+    bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    /// Received bytes that do not form a complete line yet
+    buffer: Vec<u8>,
+    /// Parsed events not yet returned, possibly ending with an error
+    pending: VecDeque<Result<Event, AskError>>,
+    done: bool,
+}
+
+/// Parses one server-sent event line into `events`. Returns `false` once the
+/// stream is over.
+fn parse_line(line: &str, events: &mut VecDeque<Event>) -> Result<bool, AskError> {
+    let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+        return Ok(true);
+    };
+    if data == "[DONE]" {
+        return Ok(false);
+    }
+    let chunk: Response = serde_json::from_str(data)
+        .map_err(|e| AskError::Parse(format!("invalid stream chunk: {}", e)))?;
+    // The final usage chunk may come without choices
+    if let Some(choice) = chunk.choices.into_iter().next() {
+        if let Some(reasoning) = choice.delta.reasoning_content {
+            events.push_back(Event::Reasoning(reasoning));
+        }
+        if let Some(content) = choice.delta.content {
+            events.push_back(Event::Delta(content));
+        }
+        if let Some(finish_reason) = choice.finish_reason {
+            events.push_back(Event::FinishReason(finish_reason));
+        }
+    }
+    if let Some(usage) = chunk.usage {
+        events.push_back(Event::Usage(usage));
+    }
+    Ok(true)
+}
+
+/// Queues the events of complete lines, marking the state done at the end of
+/// the stream or after an invalid line, whose error is queued last.
+fn parse_lines(state: &mut State, lines: &[u8]) {
+    let mut events = VecDeque::new();
+    for line in String::from_utf8_lossy(lines).lines() {
+        let parsed = parse_line(line, &mut events);
+        state.pending.extend(events.drain(..).map(Ok));
+        match parsed {
+            Ok(true) => {}
+            Ok(false) => {
+                state.done = true;
+                break;
+            }
+            Err(e) => {
+                state.pending.push_back(Err(e));
+                state.done = true;
+                break;
+            }
+        }
+    }
+}
+
+/// Turns a streamed chat completions response into [`Event`]s.
+pub fn events(response: reqwest::Response, idle_timeout: Duration) -> EventStream {
+    let bytes = response
+        .bytes_stream()
+        .map(|chunk| chunk.map(|bytes| bytes.to_vec()))
+        .boxed();
+    events_from_bytes(bytes, idle_timeout)
+}
+
+fn events_from_bytes(
+    bytes: BoxStream<'static, reqwest::Result<Vec<u8>>>,
+    idle_timeout: Duration,
+) -> EventStream {
+    let state = State {
+        bytes,
+        buffer: Vec::new(),
+        pending: VecDeque::new(),
+        done: false,
+    };
+    stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                return Some((event, state));
+            }
+            if state.done {
+                return None;
+            }
+            // Give up on a stalled stream instead of hanging forever
+            let chunk = match tokio::time::timeout(idle_timeout, state.bytes.next()).await {
+                Ok(Some(Ok(chunk))) => chunk,
+                Ok(Some(Err(e))) => {
+                    state.done = true;
+                    return Some((Err(e.into()), state));
+                }
+                Ok(None) => {
+                    // The last line may come without a newline
+                    let rest = std::mem::take(&mut state.buffer);
+                    parse_lines(&mut state, &rest);
+                    state.done = true;
+                    continue;
+                }
+                Err(_) => {
+                    state.done = true;
+                    return Some((Err(AskError::StreamStalled(idle_timeout)), state));
+                }
+            };
+            state.buffer.extend_from_slice(&chunk);
+
+            // Only complete lines are parsed, a chunk can end mid-line
+            let Some(end) = state.buffer.iter().rposition(|&byte| byte == b'\n') else {
+                continue;
+            };
+            let lines: Vec<u8> = state.buffer.drain(..=end).collect();
+            parse_lines(&mut state, &lines);
+        }
+    })
+    .boxed()
+}
+
 pub(super) async fn stream(
     response: reqwest::Response,
-) -> Result<Option<Usage>, Box<dyn std::error::Error>> {
-    let mut stream = response.bytes_stream();
-    let mut buffer = Vec::new();
-    let mut usage: Option<Usage> = None;
-
-    while let Some(item) = stream.next().await {
-        match item {
-            Ok(chunk) => {
-                buffer.extend_from_slice(&chunk);
-
-                // Convert buffer to string and process lines
-                if let Ok(text) = String::from_utf8(buffer.clone()) {
-                    if text.contains("[DONE]") {
-                        break;
-                    }
-
-                    // Process each line in the text
-                    for line in text.lines() {
-                        if line.starts_with("data: ") {
-                            let data = line.replace("data: ", "");
-                            if data == "[DONE]" {
-                                break;
-                            }
-                            match serde_json::from_str::<Response>(&data) {
-                                Ok(chunk) => {
-                                    if let Some(content) = chunk.choices[0].delta.content.as_ref() {
-                                        print!("{}", content);
-                                        // Flush immediately to show the output
-                                        std::io::stdout().flush()?;
-                                    }
-                                    // Handle the  usage information if needed
-                                    usage = chunk.usage;
-                                }
-                                Err(e) => eprintln!("Error parsing chunk: {}", e),
-                            }
-                        }
-                    }
-
-                    // Clear buffer after processing
-                    buffer.clear();
-                }
-            }
-            Err(e) => eprintln!("Stream error: {}", e),
+    printer: &mut Printer,
+    idle_timeout: Duration,
+) -> Result<(), AskError> {
+    let mut events = events(response, idle_timeout);
+    while let Some(event) = events.next().await {
+        match event? {
+            Event::Delta(content) => printer.delta(&content)?,
+            Event::Reasoning(reasoning) => printer.reasoning(&reasoning)?,
+            Event::FinishReason(finish_reason) => printer.finish_reason(finish_reason),
+            Event::Usage(usage) => printer.usage(usage),
         }
     }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_event_lines() {
+        let mut events = VecDeque::new();
+        assert!(parse_line(": keep-alive", &mut events).unwrap());
+        assert!(parse_line(
+            r#"data: {"choices":[{"delta":{"reasoning":"hm","content":"Hi"},"finish_reason":"stop"}]}"#,
+            &mut events
+        )
+        .unwrap());
+        assert!(parse_line(
+            r#"data: {"choices":[],"usage":{"prompt_tokens":1,"completion_tokens":2,"total_tokens":3}}"#,
+            &mut events
+        )
+        .unwrap());
+        assert!(!parse_line("data: [DONE]", &mut events).unwrap());
+        assert!(parse_line("data: {", &mut events).is_err());
 
-    Ok(usage)
+        let events: Vec<Event> = events.into();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], Event::Reasoning("hm".to_string()));
+        assert_eq!(events[1], Event::Delta("Hi".to_string()));
+        assert_eq!(events[2], Event::FinishReason("stop".to_string()));
+        assert!(matches!(&events[3], Event::Usage(usage) if usage.total_tokens == 3));
+    }
+
+    async fn collect(chunks: &[&str]) -> Vec<Result<Event, AskError>> {
+        let chunks: Vec<reqwest::Result<Vec<u8>>> = chunks
+            .iter()
+            .map(|chunk| Ok(chunk.as_bytes().to_vec()))
+            .collect();
+        events_from_bytes(stream::iter(chunks).boxed(), Duration::from_secs(5))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn joins_lines_split_across_chunks() {
+        let events = collect(&[
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel",
+            "lo\"}}]}\n\ndata: {\"choices\":[{\"delta\":{\"content\":\" wörld\"}}]}\n",
+            // No newline after the last line
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":1,\"completion_tokens\":2,\"total_tokens\":3}}",
+        ])
+        .await;
+        let events: Vec<Event> = events.into_iter().map(Result::unwrap).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], Event::Delta("Hello".to_string()));
+        assert_eq!(events[1], Event::Delta(" wörld".to_string()));
+        assert!(matches!(&events[2], Event::Usage(usage) if usage.total_tokens == 3));
+    }
+
+    #[tokio::test]
+    async fn stops_at_done_and_on_invalid_chunks() {
+        let events = collect(&["data: [DONE]\ndata: {\"choices\":[]}\n"]).await;
+        assert!(events.is_empty());
+
+        let events =
+            collect(&["data: {\"choices\":[{\"delta\":{\"content\":\"a\"}}]}\ndata: {\n"]).await;
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].as_ref().unwrap(), &Event::Delta("a".to_string()));
+        assert!(matches!(events[1], Err(AskError::Parse(_))));
+    }
 }
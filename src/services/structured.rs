@@ -0,0 +1,114 @@
+use crate::error::AskError;
+use crate::models::code::strip_code_fence;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// JSON output mode, with an optional schema the answer must satisfy.
+pub struct JsonMode {
+    schema: Option<Value>,
+}
+
+impl JsonMode {
+    pub fn new(schema_path: Option<&Path>) -> Result<Self, AskError> {
+        let schema = match schema_path {
+            Some(path) => {
+                let schema_str = std::fs::read_to_string(path).map_err(|e| {
+                    AskError::Usage(format!("Failed to read schema {}: {}", path.display(), e))
+                })?;
+                let schema: Value = serde_json::from_str(&schema_str).map_err(|e| {
+                    AskError::Usage(format!(
+                        "Schema {} is not valid JSON: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                jsonschema::validator_for(&schema).map_err(|e| {
+                    AskError::Usage(format!("Schema {} is invalid: {}", path.display(), e))
+                })?;
+                Some(schema)
+            }
+            None => None,
+        };
+        Ok(Self { schema })
+    }
+
+    pub fn schema(&self) -> Option<&Value> {
+        self.schema.as_ref()
+    }
+
+    /// The `response_format` field of the request body.
+    pub fn response_format(&self) -> Value {
+        match &self.schema {
+            Some(schema) => json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "response",
+                    "schema": schema
+                }
+            }),
+            None => json!({ "type": "json_object" }),
+        }
+    }
+
+    /// Parses the answer and checks it against the schema. On failure the
+    /// errors are returned as readable lines to feed back to the model.
+    pub fn validate(&self, answer: &str) -> Result<Value, Vec<String>> {
+        let value: Value = serde_json::from_str(strip_code_fence(answer))
+            .map_err(|e| vec![format!("Response is not valid JSON: {}", e)])?;
+
+        if let Some(schema) = &self.schema {
+            let validator = jsonschema::validator_for(schema).map_err(|e| vec![e.to_string()])?;
+            let errors: Vec<String> = validator
+                .iter_errors(&value)
+                .map(|error| {
+                    let path = error.instance_path().to_string();
+                    if path.is_empty() {
+                        error.to_string()
+                    } else {
+                        format!("{}: {}", path, error)
+                    }
+                })
+                .collect();
+            if !errors.is_empty() {
+                return Err(errors);
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode_with_schema(schema: Value) -> JsonMode {
+        JsonMode {
+            schema: Some(schema),
+        }
+    }
+
+    #[test]
+    fn accepts_fenced_json() {
+        let mode = JsonMode { schema: None };
+        let value = mode.validate("```json\n{\"a\": 1}\n```").unwrap();
+        assert_eq!(value, json!({"a": 1}));
+        assert_eq!(mode.response_format(), json!({"type": "json_object"}));
+    }
+
+    #[test]
+    fn reports_schema_errors() {
+        let mode = mode_with_schema(json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "required": ["name"]
+        }));
+
+        assert!(mode.validate(r#"{"name": "ask"}"#).is_ok());
+
+        let errors = mode.validate(r#"{"name": 1}"#).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("/name"));
+
+        assert!(mode.validate("not json").is_err());
+    }
+}